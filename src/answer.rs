@@ -0,0 +1,59 @@
+use std::fmt;
+
+// A day's answer, whatever type its q1/q2 natively return
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Answer {
+    Num(i64),
+    Text(String)
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Answer::Num(n) => write!(f, "{}", n),
+            Answer::Text(s) => write!(f, "{}", s)
+        }
+    }
+}
+
+impl From<usize> for Answer {
+    fn from(n: usize) -> Answer {
+        Answer::Num(n as i64)
+    }
+}
+
+impl From<u32> for Answer {
+    fn from(n: u32) -> Answer {
+        Answer::Num(n as i64)
+    }
+}
+
+impl From<i64> for Answer {
+    fn from(n: i64) -> Answer {
+        Answer::Num(n)
+    }
+}
+
+impl From<String> for Answer {
+    fn from(s: String) -> Answer {
+        Answer::Text(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answer_display_test() {
+        assert_eq!(Answer::Num(42).to_string(), "42");
+        assert_eq!(Answer::Text("abc".to_string()).to_string(), "abc");
+    }
+
+    #[test]
+    fn answer_from_test() {
+        assert_eq!(Answer::from(7usize), Answer::Num(7));
+        assert_eq!(Answer::from(802u32), Answer::Num(802));
+        assert_eq!(Answer::from("01029498".to_string()), Answer::Text("01029498".to_string()));
+    }
+}
@@ -0,0 +1,114 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::result;
+
+use answer::Answer;
+
+type Result<T> = result::Result<T, Box<dyn Error>>;
+
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
+}
+
+// FNV-1a, which unlike the std hasher gives the same hash on every run and Rust version
+fn input_hash(input: &str) -> u64 {
+    input.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// Answers already worked out, one file per (day, part, input), so a day only has to be
+// solved again once its input changes
+pub struct AnswerCache {
+    dir: PathBuf
+}
+
+impl AnswerCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> AnswerCache {
+        AnswerCache { dir: dir.into() }
+    }
+
+    fn path(&self, day: u32, part: u32, input: &str) -> PathBuf {
+        self.dir.join(format!("day{:02}_q{}_{:016x}.txt", day, part, input_hash(input)))
+    }
+
+    // None if there isn't a stored answer for this input, or it can't be read
+    pub fn get(&self, day: u32, part: u32, input: &str) -> Option<Answer> {
+        let contents = fs::read_to_string(self.path(day, part, input)).ok()?;
+
+        if let Some(n) = contents.strip_prefix("num ") {
+            n.trim().parse().ok().map(Answer::Num)
+        } else {
+            contents.strip_prefix("text\n").map(|text| Answer::Text(text.to_string()))
+        }
+    }
+
+    pub fn store(&self, day: u32, part: u32, input: &str, answer: &Answer) -> Result<()> {
+        let contents = match answer {
+            Answer::Num(n) => format!("num {}\n", n),
+            Answer::Text(text) => format!("text\n{}", text)
+        };
+
+        if let Err(e) = fs::create_dir_all(&self.dir).and_then(|_| fs::write(self.path(day, part, input), contents)) {
+            return err!("Cannot write to the answer cache in {}: {}", self.dir.display(), e);
+        }
+
+        Ok(())
+    }
+
+    // The stored answer, or else whatever compute gives, which is stored if it's not an error
+    pub fn get_or_compute<F>(&self, day: u32, part: u32, input: &str, compute: F) -> Result<Answer>
+        where F: FnOnce() -> Result<Answer>
+    {
+        if let Some(answer) = self.get(day, part, input) {
+            return Ok(answer);
+        }
+
+        let answer = compute()?;
+        self.store(day, part, input, &answer)?;
+
+        Ok(answer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn answer_cache_test() {
+        let dir = env::temp_dir().join(format!("aoc_2019_cache_test_{}", process::id()));
+        let cache = AnswerCache::new(dir.clone());
+        let computed = Cell::new(0);
+        let compute = |answer: Answer| {
+            computed.set(computed.get() + 1);
+            Ok(answer)
+        };
+
+        assert_eq!(cache.get(1, 1, "12\n14\n"), None);
+        assert_eq!(cache.get_or_compute(1, 1, "12\n14\n", || compute(Answer::Num(4))).unwrap(), Answer::Num(4));
+        assert_eq!(cache.get_or_compute(1, 1, "12\n14\n", || compute(Answer::Num(999))).unwrap(), Answer::Num(4));
+        assert_eq!(computed.get(), 1);
+
+        // a different input, or the other part, isn't in the cache yet
+        assert_eq!(cache.get_or_compute(1, 1, "1969\n", || compute(Answer::Num(654))).unwrap(), Answer::Num(654));
+        assert_eq!(cache.get(1, 2, "12\n14\n"), None);
+        assert_eq!(computed.get(), 2);
+
+        // text answers can be more than one line
+        let picture = Answer::Text(" O\n█ \n".to_string());
+        cache.store(8, 2, "0222112222120000", &picture).unwrap();
+        assert_eq!(cache.get(8, 2, "0222112222120000"), Some(picture));
+
+        // errors aren't cached
+        assert!(cache.get_or_compute(2, 1, "99", || err!("no answer")).is_err());
+        assert_eq!(cache.get(2, 1, "99"), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -22,6 +22,25 @@ fn total_fuel_requirement_for(mass: usize) -> usize {
     total_fuel
 }
 
+// The heaviest module that the given amount of fuel is enough for, fuel for the fuel included.
+// The requirement never goes down as the mass goes up, so binary search for it.
+fn max_mass_for_fuel(fuel: usize) -> usize {
+    // every 3 units of mass need at least 1 fuel, so it can't be heavier than this
+    let mut too_heavy = 3 * (fuel + 3);
+    let mut supported = 0;
+
+    while too_heavy - supported > 1 {
+        let mass = (supported + too_heavy) / 2;
+        if total_fuel_requirement_for(mass) <= fuel {
+            supported = mass;
+        } else {
+            too_heavy = mass;
+        }
+    }
+
+    supported
+}
+
 pub fn q1(fname: String) -> usize {
     let mut f = File::open(fname).expect("File not found");
     let mut f_contents = String::new();
@@ -86,4 +105,17 @@ mod tests {
             total_fuel_requirement_for(100756), 50346
         );
     }
+
+    #[test]
+    fn day01_max_mass() {
+        let fuel = total_fuel_requirement_for(100756);
+        let max_mass = max_mass_for_fuel(fuel);
+
+        assert!(max_mass >= 100756);
+        assert!(total_fuel_requirement_for(max_mass) <= fuel);
+        assert!(total_fuel_requirement_for(max_mass + 1) > fuel);
+
+        assert_eq!(max_mass_for_fuel(0), 8);
+        assert_eq!(max_mass_for_fuel(2), 14);
+    }
 }
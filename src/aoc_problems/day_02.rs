@@ -1,52 +1,25 @@
 use std::error::Error;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 use std::result;
 
-type Result<T> = result::Result<T, Box<dyn Error>>;
+use intcode::Program;
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
+type Result<T> = result::Result<T, Box<dyn Error>>;
 
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
+// Runs the program to completion and returns its final memory
+fn run_intcode(memory: Vec<i64>) -> Result<Vec<i64>> {
+    let mut program = Program::new(memory);
+    program.run_to_completion()?;
 
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
+    Ok(program.memory().to_vec())
 }
 
-fn output_value(mut numbers: Vec<usize>, noun: usize, verb: usize) -> usize {
+fn output_value(mut numbers: Vec<i64>, noun: i64, verb: i64) -> Result<i64> {
     numbers[1] = noun;
     numbers[2] = verb;
 
-    let mut pointer_idx: usize = 0;
-    loop {
-        match numbers[pointer_idx] {
-            1 => {
-                let input_1 = numbers[numbers[pointer_idx+1]];
-                let input_2 = numbers[numbers[pointer_idx+2]];
-                let output_idx = numbers[pointer_idx+3];
-                numbers[output_idx] = input_1 + input_2;
-
-                pointer_idx += 4;
-            },
-            2 => {
-                let input_1 = numbers[numbers[pointer_idx+1]];
-                let input_2 = numbers[numbers[pointer_idx+2]];
-                let output_idx = numbers[pointer_idx+3];
-                numbers[output_idx] = input_1 * input_2;
-
-                pointer_idx += 4;
-            },
-            99 => break,
-            x => panic!("Incorrect intcode: {}", x)
-        }
-    }
-
-    numbers[0]
+    Ok(run_intcode(numbers)?[0])
 }
 
 pub fn q1(fname: String) -> usize {
@@ -54,11 +27,11 @@ pub fn q1(fname: String) -> usize {
     let mut f_contents = String::new();
 
     f.read_to_string(&mut f_contents).expect("Couldn't find file");
-    let numbers: Vec<usize> = f_contents.trim().split(',').map(|x: &str| {
+    let numbers: Vec<i64> = f_contents.trim().split(',').map(|x: &str| {
         x.parse().unwrap()
     }).collect();
 
-    output_value(numbers, 12, 2)
+    output_value(numbers, 12, 2).unwrap() as usize
 }
 
 pub fn q2(fname: String) -> usize {
@@ -66,17 +39,44 @@ pub fn q2(fname: String) -> usize {
     let mut f_contents = String::new();
 
     f.read_to_string(&mut f_contents).expect("Couldn't find file");
-    let numbers: Vec<usize> = f_contents.trim().split(',').map(|x: &str| {
+    let numbers: Vec<i64> = f_contents.trim().split(',').map(|x: &str| {
         x.parse().unwrap()
     }).collect();
 
     for noun in 0..100 {
         for verb in 0..100 {
-            if output_value(numbers.clone(), noun, verb) == 19690720 {
-                return 100 * noun + verb;
+            if output_value(numbers.clone(), noun, verb).unwrap() == 19690720 {
+                return (100 * noun + verb) as usize;
             }
         }
     }
 
     panic!("Shouldn't get here");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day02_run_intcode_test() {
+        assert_eq!(
+            run_intcode(vec![1,9,10,3,2,3,11,0,99,30,40,50]).unwrap(),
+            vec![3500,9,10,70,2,3,11,0,99,30,40,50]
+        );
+        assert_eq!(run_intcode(vec![1,0,0,0,99]).unwrap(), vec![2,0,0,0,99]);
+        assert_eq!(run_intcode(vec![2,3,0,3,99]).unwrap(), vec![2,3,0,6,99]);
+        assert_eq!(run_intcode(vec![2,4,4,5,99,0]).unwrap(), vec![2,4,4,5,99,9801]);
+        assert_eq!(
+            run_intcode(vec![1,1,1,4,99,5,6,0,99]).unwrap(),
+            vec![30,1,1,4,2,5,6,0,99]
+        );
+    }
+
+    #[test]
+    fn day02_output_value_test() {
+        // noun and verb point at the two values to combine
+        assert_eq!(output_value(vec![1,0,0,0,99,30,40], 5, 6).unwrap(), 70);
+        assert_eq!(output_value(vec![2,0,0,0,99,30,40], 5, 6).unwrap(), 1200);
+    }
+}
@@ -2,7 +2,6 @@ use std::cmp;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
@@ -15,18 +14,6 @@ macro_rules! err {
     ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
-
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
 struct Coordinate {
     x: i32,
@@ -244,6 +231,11 @@ mod tests {
 
     #[test]
     fn day03_q1_tests() {
+        assert_eq!(
+            _q1("R8,U5,L5,D3".to_string(), "U7,R6,D4,L4".to_string()).unwrap(),
+            6
+        );
+
         assert_eq!(
             _q1(
                 "R75,D30,R83,U83,L12,D49,R71,U7,L72".to_string(),
@@ -263,6 +255,11 @@ mod tests {
 
     #[test]
     fn day03_q2_tests() {
+        assert_eq!(
+            _q2("R8,U5,L5,D3".to_string(), "U7,R6,D4,L4".to_string()).unwrap(),
+            30
+        );
+
         assert_eq!(
             _q2(
                 "R75,D30,R83,U83,L12,D49,R71,U7,L72".to_string(),
@@ -1,67 +1,68 @@
 use std::error::Error;
-use std::io;
-use std::io::prelude::*;
 use std::result;
 
-use std::collections::HashMap;
-
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct DigitProperties {
+    // never decreasing from left to right
+    ordered: bool,
+    // two or more of the same digit next to each other
+    has_repeat: bool,
+    // a run of exactly two of the same digit
+    has_pair: bool
+}
 
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
+// Works out everything both parts need in a single pass over the digits, which are read off
+// from the right so n never has to be turned into a string
+fn digit_properties(mut n: u32) -> DigitProperties {
+    let mut properties = DigitProperties { ordered: true, ..Default::default() };
 
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
+    let mut previous_digit = n % 10;
+    let mut run_length = 1;
+    n /= 10;
 
-fn matching_adjacent_digits(digit_list: &Vec<u32>) -> bool {
-    for t in digit_list.windows(2) {
-        if t[0] == t[1] {
-            return true;
+    while n > 0 {
+        let digit = n % 10;
+        n /= 10;
+
+        // going right to left, so the digits should never go up
+        if digit > previous_digit {
+            properties.ordered = false;
         }
-    }
-    false
-}
 
-fn matching_only_2_digits(digit_list: &Vec<u32>) -> bool {
-    let mut digit_count: HashMap<u32, u32> = HashMap::new();
+        if digit == previous_digit {
+            run_length += 1;
+        } else {
+            if run_length == 2 {
+                properties.has_pair = true;
+            }
+            run_length = 1;
+        }
+        if run_length >= 2 {
+            properties.has_repeat = true;
+        }
 
-    for &digit in digit_list {
-        let count = digit_count.entry(digit).or_insert(0);
-        *count += 1;
+        previous_digit = digit;
     }
 
-    digit_count.values().any(|&x| x == 2)
-}
-
-fn ordered_digits(digit_list: &Vec<u32>) -> bool {
-    let mut ordered = true;
-    for t in digit_list.windows(2) {
-        if t[0] > t[1] {
-            ordered = false;
-        }
+    if run_length == 2 {
+        properties.has_pair = true;
     }
 
-    ordered
+    properties
 }
 
 fn fits_password_criteria(n: u32) -> bool {
-    let digit_list = n.to_string();
-    let digit_list: Vec<_> = digit_list.chars().map(|d| d.to_digit(10).unwrap()).collect();
+    let properties = digit_properties(n);
 
-    matching_adjacent_digits(&digit_list) && ordered_digits(&digit_list)
+    properties.has_repeat && properties.ordered
 }
 
 fn fits_full_password_criteria(n: u32) -> bool {
-    let digit_list = n.to_string();
-    let digit_list: Vec<_> = digit_list.chars().map(|d| d.to_digit(10).unwrap()).collect();
+    let properties = digit_properties(n);
 
-    matching_only_2_digits(&digit_list) && ordered_digits(&digit_list)
+    properties.has_pair && properties.ordered
 }
 
 pub fn q1(start: u32, finish: u32) -> usize {
@@ -92,6 +93,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn day04_digit_properties_test() {
+        assert_eq!(
+            digit_properties(112233),
+            DigitProperties { ordered: true, has_repeat: true, has_pair: true }
+        );
+        assert_eq!(
+            digit_properties(123444),
+            DigitProperties { ordered: true, has_repeat: true, has_pair: false }
+        );
+        assert_eq!(
+            digit_properties(211),
+            DigitProperties { ordered: false, has_repeat: true, has_pair: true }
+        );
+        assert_eq!(
+            digit_properties(7),
+            DigitProperties { ordered: true, has_repeat: false, has_pair: false }
+        );
+    }
+
     #[test]
     fn day04_q2_tests() {
         assert_eq!(
@@ -1,214 +1,45 @@
 use std::error::Error;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 use std::result;
 
+use debug;
+use intcode::Program;
+
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
 macro_rules! err {
     ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
-
-#[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
-enum Parameter {
-    Position,
-    Immediate
-}
-
-#[derive(Clone, Eq, Default, Debug, PartialEq, Hash)]
-struct Instruction {
-    opcode: usize,
-    parameters: Vec<Parameter>
-}
-
-impl Instruction {
-    fn new(number: usize) -> Result<Instruction> {
-        let opcode = number % 100;
-        let mut digit_list: Vec<_> = (number / 100).to_string().chars().map(|d| d.to_digit(10).unwrap()).collect();
-        digit_list.reverse();
-
-        let params_length = match opcode {
-            1 => 3,
-            2 => 3,
-            3 => 1,
-            4 => 1,
-            5 => 2,
-            6 => 2,
-            7 => 3,
-            8 => 3,
-            99 => 0,
-            x => return err!("{}", format!("Cannot read opcode: {}", x))
-        };
-
-        digit_list.resize(params_length, 0);
-        let parameters: Result<Vec<Parameter>> = digit_list.into_iter().map(|d| match d {
-            0 => Ok(Parameter::Position),
-            1 => Ok(Parameter::Immediate),
-            x => err!("{}", format!("Cannot read parameter digit: {}", x))
-        }).collect();
-        let parameters = parameters?;
-
-        Ok(
-            Instruction {
-                opcode,
-                parameters
-            }
-        )
-
-    }
-}
-
-fn get_param(parameter_form: Parameter, val: i32, program: &Vec<i32>) -> i32 {
-    use self::Parameter::*;
+// Every value the program outputs, in order
+fn run_program(program: Vec<i32>, input_value: i32) -> Result<Vec<i32>> {
+    let mut computer = Program::new(program.into_iter().map(i64::from).collect());
+    computer.add_input(i64::from(input_value));
 
-    match parameter_form {
-        Position => program[val as usize],
-        Immediate => val
+    let mut outputs = vec![];
+    while let Some(output) = computer.run_until_output()? {
+        if debug::verbose() {
+            eprintln!("Output value: {}", output);
+        }
+        outputs.push(output as i32);
     }
+    Ok(outputs)
 }
 
-fn run_program(mut program: Vec<i32>, input_value: i32) -> Result<()> {
-
-    let mut pointer_idx = 0;
-    loop {
-        let current_instruction = Instruction::new(program[pointer_idx] as usize)?;
-        match current_instruction.opcode {
-            1 => {
-                let input_1 = get_param(
-                    current_instruction.parameters[0],
-                    program[pointer_idx+1],
-                    &program
-                );
-                let input_2 = get_param(
-                    current_instruction.parameters[1],
-                    program[pointer_idx+2],
-                    &program
-                );
-                let output_idx = program[pointer_idx+3] as usize;
-                program[output_idx] = input_1 + input_2;
-
-                pointer_idx += 4;
-            },
-            2 => {
-                let input_1 = get_param(
-                    current_instruction.parameters[0],
-                    program[pointer_idx+1],
-                    &program
-                );
-                let input_2 = get_param(
-                    current_instruction.parameters[1],
-                    program[pointer_idx+2],
-                    &program
-                );
-                let output_idx = program[pointer_idx+3] as usize;
-                program[output_idx] = input_1 * input_2;
-
-                pointer_idx += 4;
-            },
-            3 => {
-                let output_idx = program[pointer_idx+1] as usize;
-                program[output_idx] = input_value;
-
-                pointer_idx += 2;
-            },
-            4 => {
-                let input_idx = program[pointer_idx+1] as usize;
-                println!("Output value from instruction {:?}: {}", current_instruction, program[input_idx]);
-
-                pointer_idx += 2;
-            },
-            5 => {
-                let input_1 = get_param(
-                    current_instruction.parameters[0],
-                    program[pointer_idx+1],
-                    &program
-                );
-                let input_2 = get_param(
-                    current_instruction.parameters[1],
-                    program[pointer_idx+2],
-                    &program
-                );
-                if input_1 != 0 {
-                    pointer_idx = input_2 as usize;
-                } else {
-                    pointer_idx += 3;
-                }
-            },
-            6 => {
-                let input_1 = get_param(
-                    current_instruction.parameters[0],
-                    program[pointer_idx+1],
-                    &program
-                );
-                let input_2 = get_param(
-                    current_instruction.parameters[1],
-                    program[pointer_idx+2],
-                    &program
-                );
-                if input_1 == 0 {
-                    pointer_idx = input_2 as usize;
-                } else {
-                    pointer_idx += 3;
-                }
-            },
-            7 => {
-                let input_1 = get_param(
-                    current_instruction.parameters[0],
-                    program[pointer_idx+1],
-                    &program
-                );
-                let input_2 = get_param(
-                    current_instruction.parameters[1],
-                    program[pointer_idx+2],
-                    &program
-                );
-                let output_idx = program[pointer_idx+3] as usize;
-                program[output_idx] = if input_1 < input_2 {
-                    1
-                } else {
-                    0
-                };
-
-                pointer_idx += 4;
-            },
-            8 => {
-                let input_1 = get_param(
-                    current_instruction.parameters[0],
-                    program[pointer_idx+1],
-                    &program
-                );
-                let input_2 = get_param(
-                    current_instruction.parameters[1],
-                    program[pointer_idx+2],
-                    &program
-                );
-                let output_idx = program[pointer_idx+3] as usize;
-                program[output_idx] = if input_1 == input_2 {
-                    1
-                } else {
-                    0
-                };
-
-                pointer_idx += 4;
-            },
-            99 => break,
-            x => return err!("{}", format!("Incorrect opcode: {}", x))
-        }
+// The last output is the diagnostic code. Anything before it is a test result, where
+// anything other than 0 means that test failed.
+fn diagnostic_code(program: Vec<i32>, input_value: i32) -> Result<usize> {
+    let outputs = run_program(program, input_value)?;
+
+    match outputs.split_last() {
+        Some((_, tests)) if tests.iter().any(|&output| output != 0) => {
+            err!("Diagnostic tests failed: {:?}", tests)
+        },
+        Some((&code, _)) if code >= 0 => Ok(code as usize),
+        Some((&code, _)) => err!("Diagnostic code {} is negative", code),
+        None => err!("Program didn't output a diagnostic code")
     }
-    Ok(())
 }
 
 pub fn q1(fname: String) -> usize {
@@ -228,12 +59,15 @@ fn _q1(test_programs: Vec<String>) -> Result<usize> {
         s.split(',').filter_map(|ss| ss.parse::<i32>().ok()).collect()
     }).collect();
 
+    let mut code = None;
     for program in programs {
-        println!("New program");
-        run_program(program, 1)?;
+        if debug::verbose() {
+            eprintln!("New program");
+        }
+        code = Some(diagnostic_code(program, 1)?);
     }
 
-    unimplemented!();
+    code.ok_or_else(|| "No programs to run".into())
 }
 
 pub fn q2(fname: String) -> usize {
@@ -254,9 +88,51 @@ fn _q2(test_programs: Vec<String>) -> Result<usize> {
         s.split(',').filter_map(|ss| ss.parse::<i32>().ok()).collect()
     }).collect();
 
+    let mut code = None;
     for program in programs {
-        println!("New program");
-        run_program(program, 5)?;
+        if debug::verbose() {
+            eprintln!("New program");
+        }
+        code = Some(diagnostic_code(program, 5)?);
+    }
+
+    code.ok_or_else(|| "No programs to run".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(program: &str) -> Vec<i32> {
+        program.split(',').map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn day05_comparison_test() {
+        // is the input equal to 8, then less than 8, in position and then immediate mode
+        let programs = [
+            ("3,9,8,9,10,9,4,9,99,-1,8", [0, 1, 0]),
+            ("3,9,7,9,10,9,4,9,99,-1,8", [1, 0, 0]),
+            ("3,3,1108,-1,8,3,4,3,99", [0, 1, 0]),
+            ("3,3,1107,-1,8,3,4,3,99", [1, 0, 0])
+        ];
+
+        for (program, expected) in programs.iter() {
+            for (&input, &output) in [7, 8, 9].iter().zip(expected.iter()) {
+                assert_eq!(run_program(parse(program), input).unwrap(), vec![output]);
+            }
+        }
+    }
+
+    #[test]
+    fn day05_diagnostic_code_test() {
+        // 999 below 8, 1000 for 8 and 1001 above it
+        let program = parse("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99");
+        assert_eq!(diagnostic_code(program.clone(), 7).unwrap(), 999);
+        assert_eq!(diagnostic_code(program.clone(), 8).unwrap(), 1000);
+        assert_eq!(diagnostic_code(program, 9).unwrap(), 1001);
+
+        assert_eq!(_q1(vec!["3,0,104,0,104,0,4,0,99".to_string()]).unwrap(), 1);
+        assert!(_q1(vec!["3,0,104,1,104,0,4,0,99".to_string()]).is_err());
     }
-    unimplemented!();
 }
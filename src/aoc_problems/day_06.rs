@@ -1,6 +1,5 @@
 use std::error::Error;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 use std::result;
 
@@ -8,19 +7,12 @@ use std::collections::{HashMap, HashSet};
 
 use regex::Regex;
 
-type Result<T> = result::Result<T, Box<dyn Error>>;
-
+use debug;
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
+type Result<T> = result::Result<T, Box<dyn Error>>;
 
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
 struct Orbits {
@@ -104,6 +96,22 @@ impl Orbits {
         Ok(parent.clone())
     }
 
+    // Number of objects that obj orbits, directly or indirectly
+    fn depth_of(&self, obj: &str) -> Result<usize> {
+        if !self.orbit_map.contains_key(obj) {
+            return err!("Cannot find object {}", obj);
+        }
+
+        let mut depth = 0;
+        let mut current_obj = obj.to_string();
+        while let Ok(parent) = self.parent_of(&current_obj) {
+            depth += 1;
+            current_obj = parent;
+        }
+
+        Ok(depth)
+    }
+
     fn __dfs(&mut self, v: &String, dist: usize, target: &String) -> Option<usize> {
         if v == target {
             return Some(self.dist_map[&target.clone()]);
@@ -124,7 +132,9 @@ impl Orbits {
                 }
 
                 if edge == *target {
-                    println!("{:?}", self.dist_map);
+                    if debug::verbose() {
+                        eprintln!("{:?}", self.dist_map);
+                    }
                     return Some(self.dist_map[&target.clone()]);
                 }
                 if let Some(dist) = self.__dfs(&edge, new_dist, &target) {
@@ -140,12 +150,17 @@ impl Orbits {
         let source_parent = self.parent_of(&source)?;
         let target_parent = self.parent_of(&target)?;
 
-        println!("Parent of YOU = {}", source_parent);
-        println!("Parent of SAN = {}", target_parent);
+        if debug::verbose() {
+            eprintln!("Parent of {} = {}", source, source_parent);
+            eprintln!("Parent of {} = {}", target, target_parent);
+        }
 
+        self.dist_map.insert(source_parent.clone(), 0);
         let result = self.__dfs(&source_parent, 0, &target_parent);
 
-        println!("Result = {:?}", result);
+        if debug::verbose() {
+            eprintln!("Result = {:?}", result);
+        }
 
         Ok(result.unwrap())
     }
@@ -171,6 +186,10 @@ fn _q1(orbits: Vec<String>) -> Result<usize> {
 }
 
 pub fn q2(fname: String) -> usize {
+    q2_between(fname, "YOU", "SAN")
+}
+
+pub fn q2_between(fname: String, source: &str, target: &str) -> usize {
     let mut f = File::open(fname).expect("File not found");
     let mut f_contents = String::new();
 
@@ -179,13 +198,17 @@ pub fn q2(fname: String) -> usize {
         x.trim().to_string()
     }).collect();
 
-    _q2(orbits).unwrap()
+    _q2_between(orbits, source, target).unwrap()
 }
 
 fn _q2(orbits: Vec<String>) -> Result<usize> {
+    _q2_between(orbits, "YOU", "SAN")
+}
+
+fn _q2_between(orbits: Vec<String>, source: &str, target: &str) -> Result<usize> {
     let mut orbit_info = Orbits::new(orbits)?;
 
-    orbit_info.shortest_path_from("YOU".to_string(), "SAN".to_string())
+    orbit_info.shortest_path_from(source.to_string(), target.to_string())
 }
 
 #[cfg(test)]
@@ -215,6 +238,30 @@ mod tests {
         )
     }
 
+    #[test]
+    fn day06_depth_test() {
+        let orbits_str: Vec<String> = "
+            COM)B
+            B)C
+            C)D
+            D)E
+            E)F
+            B)G
+            G)H
+            D)I
+            E)J
+            J)K
+            K)L
+            ".to_string().trim().lines().map(|x: &str| {
+                x.trim().to_string()
+            }).collect();
+        let orbit_info = Orbits::new(orbits_str).unwrap();
+
+        assert_eq!(orbit_info.depth_of("D").unwrap(), 3);
+        assert_eq!(orbit_info.depth_of("L").unwrap(), 7);
+        assert!(orbit_info.depth_of("X").is_err());
+    }
+
     #[test]
     fn day06_q2_test() {
         let orbits_str: Vec<String> = "
@@ -239,4 +286,36 @@ mod tests {
             4
         )
     }
+
+    #[test]
+    fn day06_q2_other_endpoints_test() {
+        let orbits_str: Vec<String> = "
+            COM)B
+            B)C
+            C)D
+            D)E
+            E)F
+            B)G
+            G)H
+            D)I
+            E)J
+            J)K
+            K)L
+            ".to_string().trim().lines().map(|x: &str| {
+                x.trim().to_string()
+            }).collect();
+
+        assert_eq!(
+            _q2_between(orbits_str.clone(), "L", "I").unwrap(),
+            3
+        );
+        assert_eq!(
+            _q2_between(orbits_str.clone(), "H", "F").unwrap(),
+            4
+        );
+        assert_eq!(
+            _q2_between(orbits_str, "E", "I").unwrap(),
+            0
+        );
+    }
 }
@@ -2,25 +2,18 @@ use std::cmp;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
 
 use std::collections::BTreeMap;
 
-type Result<T> = result::Result<T, Box<dyn Error>>;
-
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
+use debug;
 
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
+type Result<T> = result::Result<T, Box<dyn Error>>;
 
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
@@ -122,6 +115,8 @@ impl fmt::Display for PixelType {
 
 struct Picture {
     layers: Vec<BTreeMap<Coordinate, PixelType>>,
+    // the layers stacked on top of each other; Transparent where every layer is transparent
+    composite: BTreeMap<Coordinate, PixelType>,
     width: u32,
     height: u32,
     layer_count: usize
@@ -129,8 +124,18 @@ struct Picture {
 
 impl Picture {
     fn new(mut pixels: Vec<u32>, width: u32, height: u32) -> Result<Picture> {
-        let layer_count = pixels.len() / (width * height) as usize;
-        println!("Layer count is {}", layer_count);
+        let layer_size = (width * height) as usize;
+        if layer_size == 0 || !pixels.len().is_multiple_of(layer_size) {
+            return err!(
+                "Expected a multiple of {}x{} = {} pixels, got {}",
+                width, height, layer_size, pixels.len()
+            );
+        }
+
+        let layer_count = pixels.len() / layer_size;
+        if debug::verbose() {
+            eprintln!("Layer count is {}", layer_count);
+        }
 
         let mut layers: Vec<_> = (0..layer_count).map(|_| BTreeMap::new()).collect();
 
@@ -142,26 +147,68 @@ impl Picture {
             }
         }
 
+        let mut composite = BTreeMap::new();
+        for y in 0..height {
+            for x in 0..width {
+                let coord = Coordinate::new(x, y);
+                let pixel = layers.iter()
+                    .map(|layer| layer[&coord])
+                    .find(|&pixel| pixel != PixelType::Transparent)
+                    .unwrap_or(PixelType::Transparent);
+
+                composite.insert(coord, pixel);
+            }
+        }
+
         Ok(
             Picture {
-                layers, width, height, layer_count
+                layers, composite, width, height, layer_count
             }
         )
     }
 
-    fn first_opaque_pixel(&self, x: u32, y: u32) -> PixelType {
-        use self::PixelType::*;
-        for layer in 0..self.layer_count {
-            if let Some(pixel_type) = self.layers[layer].get(&Coordinate::new(x, y)) {
-                let pixel = match *pixel_type {
-                    Transparent => continue,
-                    pixel => pixel
-                };
+    fn layer(&self, idx: usize) -> Option<&BTreeMap<Coordinate, PixelType>> {
+        self.layers.get(idx)
+    }
+
+    // A single layer drawn on its own, before any compositing. Empty if there's no such layer.
+    fn render_layer(&self, idx: usize) -> String {
+        let layer = match self.layer(idx) {
+            Some(layer) => layer,
+            None => return String::new()
+        };
 
-                return pixel;
+        let mut rendered = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                rendered.push_str(&layer[&Coordinate::new(x, y)].to_string());
+            }
+            rendered.push('\n');
+        }
+
+        rendered
+    }
+
+    // The message the layers spell out, drawn row by row. Errors if any pixel is transparent
+    // all the way down, since then there's nothing to draw for it.
+    fn decode(&self) -> Result<String> {
+        let mut message = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                message.push_str(&self.first_opaque_pixel(x, y)?.to_string());
             }
+            message.push('\n');
+        }
+
+        Ok(message)
+    }
+
+    fn first_opaque_pixel(&self, x: u32, y: u32) -> Result<PixelType> {
+        match self.composite.get(&Coordinate::new(x, y)) {
+            Some(PixelType::Transparent) => err!("Pixel ({}, {}) is transparent in every layer", x, y),
+            Some(&pixel) => Ok(pixel),
+            None => err!("Pixel ({}, {}) is outside the {}x{} picture", x, y, self.width, self.height)
         }
-        panic!("Cannot find pixel");
     }
 }
 
@@ -191,9 +238,10 @@ impl fmt::Debug for Picture {
 
 impl fmt::Display for Picture {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // pixels that are transparent all the way down are drawn as transparent
         for y in 0..self.height {
             for x in 0..self.width {
-                write!(f, "{}", self.first_opaque_pixel(x, y))?;
+                write!(f, "{}", self.composite[&Coordinate::new(x, y)])?;
             }
             write!(f, "{}", '\n')?;
         }
@@ -225,7 +273,9 @@ fn _q1(mut pixels: Vec<u32>) -> Result<usize> {
         .ok_or("No elements!")?
         .0;
 
-    println!("Layer with least zeroes = {}", zero_layer_min);
+    if debug::verbose() {
+        eprintln!("Layer with least zeroes = {}", zero_layer_min);
+    }
 
     Ok(
         picture.layers[zero_layer_min].values().filter(|&&pixel| pixel == PixelType::White).count()
@@ -233,7 +283,7 @@ fn _q1(mut pixels: Vec<u32>) -> Result<usize> {
     )
 }
 
-pub fn q2(fname: String) -> usize {
+pub fn q2(fname: String) -> String {
     let mut f = File::open(fname).expect("File not found");
     let mut f_contents = String::new();
 
@@ -245,13 +295,20 @@ pub fn q2(fname: String) -> usize {
     _q2(pixel_data).unwrap()
 }
 
-fn _q2(mut pixels: Vec<u32>) -> Result<usize> {
+fn _q2(pixels: Vec<u32>) -> Result<String> {
+    decode_message(pixels, 25, 6)
+}
+
+fn decode_message(mut pixels: Vec<u32>, width: u32, height: u32) -> Result<String> {
     pixels.reverse();
-    let picture = Picture::new(pixels, 25, 6)?;
+    let picture = Picture::new(pixels, width, height)?;
 
-    print!("{}", picture);
+    let message = picture.decode()?;
+    if debug::verbose() {
+        eprint!("{}", message);
+    }
 
-    unimplemented!();
+    Ok(message)
 }
 
 #[cfg(test)]
@@ -263,7 +320,59 @@ mod tests {
         let mut pixels = vec![0,2,2,2,1,1,2,2,2,2,1,2,0,0,0,0];
         pixels.reverse();
         let picture = Picture::new(pixels, 2, 2).unwrap();
-        print!("{:?}", picture);
-        print!("{}", picture);
+        eprint!("{:?}", picture);
+        eprint!("{}", picture);
+    }
+
+    #[test]
+    fn day08_decode_message_test() {
+        assert_eq!(
+            decode_message(vec![0,2,2,2,1,1,2,2,2,2,1,2,0,0,0,0], 2, 2).unwrap(),
+            " █\n█ \n"
+        );
+
+        // the top right pixel is transparent in both layers
+        assert!(decode_message(vec![0,2,1,0,1,2,0,1], 2, 2).is_err());
+    }
+
+    #[test]
+    fn day08_render_layer_test() {
+        let mut pixels = vec![0,2,2,2,1,1,2,2,2,2,1,2,0,0,0,0];
+        pixels.reverse();
+        let picture = Picture::new(pixels, 2, 2).unwrap();
+
+        assert_eq!(picture.render_layer(0), " O\nOO\n");
+        assert_eq!(picture.render_layer(3), "  \n  \n");
+        assert_eq!(picture.render_layer(4), "");
+        assert!(picture.layer(4).is_none());
+        assert_eq!(picture.layer(1).map(|layer| layer.len()), Some(4));
+    }
+
+    #[test]
+    fn day08_transparent_pixel_test() {
+        // the top right pixel is transparent in both layers
+        let mut pixels = vec![0,2,1,0,1,2,0,1];
+        pixels.reverse();
+        let picture = Picture::new(pixels, 2, 2).unwrap();
+
+        assert!(picture.first_opaque_pixel(0, 0).unwrap() == PixelType::Black);
+        assert!(picture.first_opaque_pixel(0, 1).unwrap() == PixelType::White);
+        match picture.first_opaque_pixel(1, 0) {
+            Err(e) => assert_eq!(e.to_string(), "Pixel (1, 0) is transparent in every layer"),
+            Ok(_) => panic!("Pixel (1, 0) should be transparent in every layer")
+        }
+        assert!(picture.first_opaque_pixel(2, 0).is_err());
+
+        assert_eq!(picture.to_string(), " O\n█ \n");
+    }
+
+    #[test]
+    fn day08_uneven_length_test() {
+        let pixels = vec![0; 25 * 6 + 7];
+
+        match Picture::new(pixels, 25, 6) {
+            Err(e) => assert_eq!(e.to_string(), "Expected a multiple of 25x6 = 150 pixels, got 157"),
+            Ok(_) => panic!("Picture with a partial layer should be rejected")
+        }
     }
 }
@@ -1,27 +1,18 @@
 use std::cmp;
+use std::f64::consts::PI;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 
-type Result<T> = result::Result<T, Box<dyn Error>>;
-
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
+use debug;
+use math::gcd;
 
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
+type Result<T> = result::Result<T, Box<dyn Error>>;
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
 struct Coordinate {
@@ -125,7 +116,7 @@ impl fmt::Display for SpaceType {
     }
 }
 
-#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 struct LineOfSight {
     x: i32,
     y: i32,
@@ -140,11 +131,79 @@ impl LineOfSight {
             y: y / input_gcd,
         }
     }
+
+    fn angle(&self) -> Angle {
+        Angle::new(self.x, self.y)
+    }
+
+    // In radians, clockwise from straight up, in [0, 2π). Fine for showing to people, but
+    // anything sorting by it should use angle() so that close directions never tie.
+    fn clockwise_angle(&self) -> f64 {
+        let angle = (self.x as f64).atan2(-self.y as f64);
+        if angle < 0.0 {
+            angle + 2.0 * PI
+        } else {
+            angle
+        }
+    }
+}
+
+// The clockwise angle from straight up that the laser at from has to turn to point at to
+pub fn clockwise_angle(from: (u32, u32), to: (u32, u32)) -> f64 {
+    LineOfSight::new(to.0 as i32 - from.0 as i32, to.1 as i32 - from.1 as i32).clockwise_angle()
+}
+
+// Exact clockwise angle of a direction, measured from straight up (remembering y points down),
+// so sorting by it gives the order the laser sweeps in without any floating point.
+// Within each quadrant the direction is (along, across) relative to the quadrant's first axis,
+// and the angle grows with across / along.
+#[derive(Clone, Copy, Debug, Eq)]
+struct Angle {
+    quadrant: u8,
+    along: i32,
+    across: i32
+}
+
+impl Angle {
+    fn new(dx: i32, dy: i32) -> Angle {
+        let (quadrant, along, across) = if dx >= 0 && dy < 0 {
+            (0, -dy, dx)
+        } else if dx > 0 && dy >= 0 {
+            (1, dx, dy)
+        } else if dx <= 0 && dy > 0 {
+            (2, dy, -dx)
+        } else {
+            (3, -dx, -dy)
+        };
+
+        Angle { quadrant, along, across }
+    }
+}
+
+impl Ord for Angle {
+    fn cmp(&self, other: &Angle) -> cmp::Ordering {
+        self.quadrant.cmp(&other.quadrant)
+            .then((self.across * other.along).cmp(&(other.across * self.along)))
+    }
+}
+
+impl PartialOrd for Angle {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Angle {
+    fn eq(&self, other: &Angle) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Hash)]
 struct AsteroidField {
-    map: BTreeMap<Coordinate, SpaceType>
+    map: BTreeMap<Coordinate, SpaceType>,
+    // every asteroid in map, in map order, so they only have to be picked out once
+    asteroids: Vec<Coordinate>
 }
 
 impl AsteroidField {
@@ -160,49 +219,78 @@ impl AsteroidField {
             }
         }
 
+        let asteroids: Vec<Coordinate> = asteroid_map
+            .iter()
+            .filter(|&(_, square)| square.is_asteroid())
+            .map(|(&c, _)| c)
+            .collect();
+
         Ok(
             AsteroidField {
-                map: asteroid_map
+                map: asteroid_map,
+                asteroids
             }
         )
     }
 
     fn most_visible_asteroid(&self) -> (Coordinate, usize) {
-        let asteroid_coords: Vec<Coordinate> = self.map
-            .iter()
-            .filter_map(|(&c, square)| {
-                    if square.is_asteroid() {
-                        Some(c)
-                    } else { None }
-                }
-            ).collect();
-
-        let visible_asteroids: Vec<(Coordinate, usize)> = asteroid_coords
-            .iter()
-            .map(|&c|
-                (c, number_of_visible_coords(c, asteroid_coords.clone()))
-            ).collect();
+        // ties go to the later asteroid
+        let mut best: Option<(Coordinate, usize)> = None;
+        for &c in &self.asteroids {
+            let to_match = best.map_or(0, |(_, count)| count);
+            if let Some(count) = number_of_visible_coords(c, &self.asteroids, to_match) {
+                best = Some((c, count));
+            }
+        }
 
-        *visible_asteroids.iter().max_by_key(|t| t.1).unwrap()
+        best.unwrap()
     }
 }
 
-fn number_of_visible_coords(current_coord: Coordinate, coords: Vec<Coordinate>) -> usize {
+// None if current_coord can't see at least to_match asteroids, which is given up on as soon as
+// there aren't enough asteroids left to check for it to get there
+fn number_of_visible_coords(current_coord: Coordinate, coords: &[Coordinate], to_match: usize) -> Option<usize> {
     let mut lines_of_sight: HashSet<LineOfSight> = HashSet::new();
 
-    for coord in coords {
+    for (idx, &coord) in coords.iter().enumerate() {
+        if lines_of_sight.len() + (coords.len() - idx) < to_match {
+            return None;
+        }
+
         if current_coord == coord {
             continue;
         }
 
         lines_of_sight.insert(
             LineOfSight::new(
-                (coord.y as i32) - (current_coord.y as i32),
                 (coord.x as i32) - (current_coord.x as i32),
+                (coord.y as i32) - (current_coord.y as i32),
             )
         );
     }
 
+    if lines_of_sight.len() >= to_match {
+        Some(lines_of_sight.len())
+    } else {
+        None
+    }
+}
+
+// Same count as number_of_visible_coords, but by sorting the directions and counting the
+// distinct ones rather than hashing them
+fn sorted_number_of_visible_coords(current_coord: Coordinate, coords: &[Coordinate]) -> usize {
+    let mut lines_of_sight: Vec<LineOfSight> = coords
+        .iter()
+        .filter(|&&coord| coord != current_coord)
+        .map(|coord| LineOfSight::new(
+            (coord.x as i32) - (current_coord.x as i32),
+            (coord.y as i32) - (current_coord.y as i32),
+        ))
+        .collect();
+
+    lines_of_sight.sort();
+    lines_of_sight.dedup();
+
     lines_of_sight.len()
 }
 
@@ -214,23 +302,23 @@ fn line_of_sight_info(current_coord: Coordinate, coords: Vec<Coordinate>) -> Has
             continue;
         }
 
-        let dy = (coord.y as i32) - (current_coord.y as i32);
         let dx = (coord.x as i32) - (current_coord.x as i32);
-        let line_of_sight_info = LineOfSight::new(dy, dx);
+        let dy = (coord.y as i32) - (current_coord.y as i32);
+        let line_of_sight_info = LineOfSight::new(dx, dy);
 
         // if line of sight hasn't already been seen, or if this line of sight is closer
         // than already added
-        if !lines_of_sight.contains_key(&line_of_sight_info)
-            || (dx.abs() < (lines_of_sight[&line_of_sight_info].x as i32 - current_coord.x as i32).abs())
-        {
-            lines_of_sight.insert(
-                LineOfSight::new(
-                    (coord.y as i32) - (current_coord.y as i32),
-                    (coord.x as i32) - (current_coord.x as i32),
-                ),
-                coord
-            );
-
+        let is_closer = match lines_of_sight.get(&line_of_sight_info) {
+            Some(&seen_coord) => {
+                dx.abs() + dy.abs()
+                    < (seen_coord.x as i32 - current_coord.x as i32).abs()
+                        + (seen_coord.y as i32 - current_coord.y as i32).abs()
+            },
+            None => true
+        };
+
+        if is_closer {
+            lines_of_sight.insert(line_of_sight_info, coord);
         }
 
     }
@@ -238,22 +326,6 @@ fn line_of_sight_info(current_coord: Coordinate, coords: Vec<Coordinate>) -> Has
     lines_of_sight
 }
 
-fn gcd(m: i32, n: i32) -> i32 {
-    if m == 0 {
-        n.abs()
-    } else {
-        gcd(n % m, m)
-    }
-}
-
-fn gradient_of(line_of_sight: LineOfSight) -> f32 {
-    match (line_of_sight.x, line_of_sight.y) {
-        (x, 0) if x > 0 => std::f32::INFINITY,
-        (x, 0) if x < 0 => std::f32::NEG_INFINITY,
-        (x, y) => (x as f32) / (y as f32)
-    }
-}
-
 impl fmt::Display for AsteroidField {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut current_y = 0;
@@ -288,7 +360,9 @@ fn _q1(asteroid_data: Vec<Vec<char>>) -> Result<usize> {
 
     let most_visible_asteroid = asteroid_field.most_visible_asteroid();
 
-    println!("Most visible asteroid = {:?}", most_visible_asteroid);
+    if debug::verbose() {
+        eprintln!("Most visible asteroid = {:?}", most_visible_asteroid);
+    }
 
     Ok(most_visible_asteroid.1)
 }
@@ -305,51 +379,68 @@ pub fn q2(fname: String) -> u32 {
     _q2(asteroid_data).unwrap()
 }
 
-fn _q2(asteroid_data: Vec<Vec<char>>) -> Result<u32> {
-    let asteroid_field = AsteroidField::new(asteroid_data)?;
+// Clockwise from straight up; the coordinate breaks any ties so the order never
+// depends on HashMap iteration
+fn sort_clockwise(sightlines: &mut [(LineOfSight, Coordinate)]) {
+    sightlines.sort_by(|&(los_1, coord_1), &(los_2, coord_2)| {
+        los_1.angle().cmp(&los_2.angle()).then(coord_1.cmp(&coord_2))
+    });
+}
 
-    let station_coord = asteroid_field.most_visible_asteroid().0;
+// Every other asteroid in the order the laser vaporizes them, going round as many times as it takes
+fn vaporization_order(station_coord: Coordinate, mut asteroid_coords: Vec<Coordinate>) -> Vec<Coordinate> {
+    asteroid_coords.retain(|&c| c != station_coord);
 
-    let asteroid_coords: Vec<Coordinate> = asteroid_field.map.iter().filter_map(|(&c, square)| {
-            if square.is_asteroid() && (station_coord != c) {
-                Some(c)
-            } else { None }
-        }
-    ).collect();
-
-    // Don't have to worry about the points behind, as I only need to count up to 200
-    // and from part 1, there are > 300 in sight
-    let line_of_sight_info = line_of_sight_info(station_coord, asteroid_coords);
-
-    // Going clockwise: split into 2 parts
-    // Right of station: blow up in decreasing gradient
-    // Left of station: blow up in increasing gradient
-    let mut first_half: Vec<_> = line_of_sight_info.iter().filter_map(|(&line_of_sight, &coord)| {
-        if (coord.x > station_coord.x)
-            || (coord.x == station_coord.x) && (coord.y > station_coord.y)
-        {
-            Some((coord, gradient_of(line_of_sight)))
-        } else { None }
-    }).collect();
+    let mut vaporization_order = Vec::new();
+    while !asteroid_coords.is_empty() {
+        // Going clockwise from straight up, only the closest asteroid on each line goes this time round
+        let mut rotation: Vec<(LineOfSight, Coordinate)> = line_of_sight_info(station_coord, asteroid_coords.clone())
+            .into_iter()
+            .collect();
+        sort_clockwise(&mut rotation);
 
-    // reverse sorting
-    first_half.sort_by(|t1, t2| t2.1.partial_cmp(&t1.1).unwrap());
+        let vaporized: HashSet<Coordinate> = rotation.iter().map(|&(_, c)| c).collect();
+        asteroid_coords.retain(|c| !vaporized.contains(c));
 
-    let mut second_half: Vec<_> = line_of_sight_info.iter().filter_map(|(&line_of_sight, &coord)| {
-        if (coord.x < station_coord.x)
-            || (coord.x == station_coord.x) && (coord.y < station_coord.y)
-        {
-            Some((coord, gradient_of(line_of_sight)))
-        } else { None }
+        vaporization_order.extend(rotation.into_iter().map(|(_, c)| c));
+    }
+
+    vaporization_order
+}
+
+// The first n asteroids vaporized from the best station, or all of them if there are fewer
+pub fn q2_top_n(fname: String, n: usize) -> Vec<(u32, u32)> {
+    let mut f = File::open(fname).expect("File not found");
+    let mut f_contents = String::new();
+
+    f.read_to_string(&mut f_contents).expect("Couldn't find file");
+    let asteroid_data: Vec<Vec<char>> = f_contents.trim().lines().map(|x| {
+        x.chars().collect()
     }).collect();
-    second_half.sort_by(|t1, t2| t1.1.partial_cmp(&t2.1).unwrap());
 
-    first_half.extend(second_half);
+    _q2_top_n(asteroid_data, n).unwrap().into_iter().map(|c| (c.x, c.y)).collect()
+}
+
+fn _q2_top_n(asteroid_data: Vec<Vec<char>>, n: usize) -> Result<Vec<Coordinate>> {
+    let asteroid_field = AsteroidField::new(asteroid_data)?;
+
+    let station_coord = asteroid_field.most_visible_asteroid().0;
+
+    let mut vaporized = vaporization_order(station_coord, asteroid_field.asteroids.clone());
+    vaporized.truncate(n);
+
+    Ok(vaporized)
+}
 
+fn _q2(asteroid_data: Vec<Vec<char>>) -> Result<u32> {
     // Looking for 200th
-    let relevant_coord: Coordinate = first_half[199].0;
+    let relevant_coord: Coordinate = *_q2_top_n(asteroid_data, 200)?
+        .get(199)
+        .ok_or("Fewer than 200 asteroids to vaporize")?;
 
-    println!("200th coordinate = {}", relevant_coord);
+    if debug::verbose() {
+        eprintln!("200th coordinate = {}", relevant_coord);
+    }
 
     Ok((relevant_coord.x * 100) + relevant_coord.y)
 }
@@ -358,6 +449,30 @@ fn _q2(asteroid_data: Vec<Vec<char>>) -> Result<u32> {
 mod tests {
     use super::*;
 
+    // the largest example in the puzzle, the one the vaporizing order is given for
+    const LARGE_SAMPLE: &str = "
+        .#..##.###...#######
+        ##.############..##.
+        .#.######.########.#
+        .###.#######.####.#.
+        #####.##.#.##.###.##
+        ..#####..#.#########
+        ####################
+        #.####....###.#.#.##
+        ##.#################
+        #####.##.###..####..
+        ..######..##.#######
+        ####.##.####...##..#
+        .#####..#.######.###
+        ##...#.##########...
+        #.##########.#######
+        .####.#.###.###.#.##
+        ....##.##.###..#####
+        .#.#.###########.###
+        #.#.#.#####.####.###
+        ###.##.####.##.#..##
+    ";
+
     #[test]
     fn day10_q1_test1() {
         let asteroid_data : Vec<Vec<char>> = "
@@ -439,28 +554,7 @@ mod tests {
 
     #[test]
     fn day10_q1_test5() {
-        let asteroid_data : Vec<Vec<char>> = "
-            .#..##.###...#######
-            ##.############..##.
-            .#.######.########.#
-            .###.#######.####.#.
-            #####.##.#.##.###.##
-            ..#####..#.#########
-            ####################
-            #.####....###.#.#.##
-            ##.#################
-            #####.##.###..####..
-            ..######..##.#######
-            ####.##.####...##..#
-            .#####..#.######.###
-            ##...#.##########...
-            #.##########.#######
-            .####.#.###.###.#.##
-            ....##.##.###..#####
-            .#.#.###########.###
-            #.#.#.#####.####.###
-            ###.##.####.##.#..##
-        ".trim().lines().map(|l| l.trim().chars().collect()).collect();
+        let asteroid_data : Vec<Vec<char>> = LARGE_SAMPLE.trim().lines().map(|l| l.trim().chars().collect()).collect();
 
         assert_eq!(
             _q1(asteroid_data).unwrap(),
@@ -470,32 +564,148 @@ mod tests {
 
     #[test]
     fn day10_q2_test() {
-        let asteroid_data : Vec<Vec<char>> = "
-            .#..##.###...#######
-            ##.############..##.
-            .#.######.########.#
-            .###.#######.####.#.
-            #####.##.#.##.###.##
-            ..#####..#.#########
-            ####################
-            #.####....###.#.#.##
-            ##.#################
-            #####.##.###..####..
-            ..######..##.#######
-            ####.##.####...##..#
-            .#####..#.######.###
-            ##...#.##########...
-            #.##########.#######
-            .####.#.###.###.#.##
-            ....##.##.###..#####
-            .#.#.###########.###
-            #.#.#.#####.####.###
-            ###.##.####.##.#..##
-        ".trim().lines().map(|l| l.trim().chars().collect()).collect();
+        let asteroid_data : Vec<Vec<char>> = LARGE_SAMPLE.trim().lines().map(|l| l.trim().chars().collect()).collect();
 
         assert_eq!(
             _q2(asteroid_data).unwrap(),
            802
         )
     }
+
+    #[test]
+    fn day10_q2_top_n_test() {
+        let asteroid_data : Vec<Vec<char>> = LARGE_SAMPLE.trim().lines().map(|l| l.trim().chars().collect()).collect();
+
+        let vaporized = _q2_top_n(asteroid_data, 400).unwrap();
+
+        // the station at (11, 13) has 299 other asteroids to vaporize
+        assert_eq!(vaporized.len(), 299);
+        let checks = [
+            (1, (11, 12)), (2, (12, 1)), (3, (12, 2)), (10, (12, 8)), (20, (16, 0)),
+            (50, (16, 9)), (100, (10, 16)), (199, (9, 6)), (200, (8, 2)), (201, (10, 9)),
+            (299, (11, 1)),
+        ];
+        for &(nth, (x, y)) in checks.iter() {
+            assert_eq!(vaporized[nth - 1], Coordinate::new(x, y), "asteroid number {}", nth);
+        }
+    }
+
+    #[test]
+    fn day10_angle_order_test() {
+        let up = LineOfSight::new(0, -3);
+        let right = LineOfSight::new(2, 0);
+        let down = LineOfSight::new(0, 1);
+        let left = LineOfSight::new(-5, 0);
+
+        assert!(up.angle() < right.angle());
+        assert!(right.angle() < down.angle());
+        assert!(down.angle() < left.angle());
+
+        // in between the cardinal directions
+        assert!(up.angle() < LineOfSight::new(1, -2).angle());
+        assert!(LineOfSight::new(1, -2).angle() < LineOfSight::new(2, -1).angle());
+        assert!(LineOfSight::new(2, -1).angle() < right.angle());
+        assert!(left.angle() < LineOfSight::new(-1, -1).angle());
+        assert_eq!(LineOfSight::new(-1, -1).angle(), LineOfSight::new(-3, -3).angle());
+    }
+
+    #[test]
+    fn day10_near_vertical_order_test() {
+        let station = Coordinate::new(1, 101);
+        let asteroids = vec![
+            Coordinate::new(0, 1),
+            Coordinate::new(2, 1),
+            Coordinate::new(1, 100),
+        ];
+
+        let expected = vec![
+            Coordinate::new(1, 100),
+            Coordinate::new(2, 1),
+            Coordinate::new(0, 1),
+        ];
+
+        for start in 0..asteroids.len() {
+            let mut rotated = asteroids.clone();
+            rotated.rotate_left(start);
+
+            let mut sightlines: Vec<(LineOfSight, Coordinate)> =
+                line_of_sight_info(station, rotated).into_iter().collect();
+            sort_clockwise(&mut sightlines);
+
+            let order: Vec<Coordinate> = sightlines.into_iter().map(|(_, c)| c).collect();
+            assert_eq!(order, expected);
+        }
+    }
+
+    #[test]
+    fn day10_sorted_count_test() {
+        let asteroid_data : Vec<Vec<char>> = LARGE_SAMPLE.trim().lines().map(|l| l.trim().chars().collect()).collect();
+
+        let asteroid_field = AsteroidField::new(asteroid_data).unwrap();
+        let asteroid_coords: Vec<Coordinate> = asteroid_field.map.iter()
+            .filter(|&(_, square)| square.is_asteroid())
+            .map(|(&c, _)| c)
+            .collect();
+
+        for &c in &asteroid_coords {
+            assert_eq!(
+                Some(sorted_number_of_visible_coords(c, &asteroid_coords)),
+                number_of_visible_coords(c, &asteroid_coords, 0)
+            );
+        }
+        assert_eq!(
+            asteroid_coords.iter().map(|&c| sorted_number_of_visible_coords(c, &asteroid_coords)).max(),
+            Some(210)
+        );
+    }
+
+    #[test]
+    fn day10_cached_asteroids_test() {
+        let asteroid_data : Vec<Vec<char>> = "
+            .#..#
+            .....
+            #####
+            ....#
+            ...##
+        ".trim().lines().map(|l| l.trim().chars().collect()).collect();
+
+        let asteroid_field = AsteroidField::new(asteroid_data).unwrap();
+        let filtered: Vec<Coordinate> = asteroid_field.map.iter()
+            .filter(|&(_, square)| square.is_asteroid())
+            .map(|(&c, _)| c)
+            .collect();
+
+        assert_eq!(asteroid_field.asteroids, filtered);
+        assert_eq!(
+            asteroid_field.asteroids,
+            vec![
+                Coordinate::new(1, 0), Coordinate::new(4, 0),
+                Coordinate::new(0, 2), Coordinate::new(1, 2), Coordinate::new(2, 2),
+                Coordinate::new(3, 2), Coordinate::new(4, 2),
+                Coordinate::new(4, 3),
+                Coordinate::new(3, 4), Coordinate::new(4, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn day10_clockwise_angle_test() {
+        assert_eq!(LineOfSight::new(0, -3).clockwise_angle(), 0.0);
+        assert_eq!(LineOfSight::new(2, 0).clockwise_angle(), PI / 2.0);
+        assert_eq!(LineOfSight::new(0, 5).clockwise_angle(), PI);
+        assert_eq!(LineOfSight::new(-1, 0).clockwise_angle(), 3.0 * PI / 2.0);
+
+        // from the station at (8, 3) in the q2 example, (9, 0) is just past straight up
+        let angle = clockwise_angle((8, 3), (9, 0));
+        assert!(angle > 0.0 && angle < PI / 8.0);
+
+        // agrees with the exact ordering used for vaporizing
+        let mut sightlines: Vec<LineOfSight> = vec![(1, -5), (-1, -5), (5, 1), (-3, 2), (0, 1), (1, -1)]
+            .into_iter()
+            .map(|(x, y)| LineOfSight::new(x, y))
+            .collect();
+        sightlines.sort_by_key(|los| los.angle());
+        let angles: Vec<f64> = sightlines.iter().map(|los| los.clockwise_angle()).collect();
+        assert!(angles.windows(2).all(|pair| pair[0] < pair[1]));
+    }
 }
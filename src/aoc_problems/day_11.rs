@@ -1,32 +1,25 @@
 use std::cmp;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fs::File;
 use std::fmt;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
+use std::str::FromStr;
 
 use std::collections::BTreeMap;
 
+use debug;
+use intcode::Program;
+use tile_map::TileMap;
+
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
 macro_rules! err {
     ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
-
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
 struct Coordinate {
     x: i32,
@@ -94,276 +87,6 @@ impl fmt::Display for Coordinate {
     }
 }
 
-#[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
-enum Parameter {
-    Position,
-    Immediate,
-    Relative
-}
-
-#[derive(Clone, Eq, Default, Debug, PartialEq, Hash)]
-struct Instruction {
-    opcode: usize,
-    parameters: Vec<Parameter>
-}
-
-impl Instruction {
-    fn new(number: usize) -> Result<Instruction> {
-        let opcode = number % 100;
-        let mut digit_list: Vec<_> = (number / 100).to_string().chars().map(|d| d.to_digit(10).unwrap()).collect();
-        digit_list.reverse();
-
-        let params_length = match opcode {
-            1 => 3,
-            2 => 3,
-            3 => 1,
-            4 => 1,
-            5 => 2,
-            6 => 2,
-            7 => 3,
-            8 => 3,
-            9 => 1,
-            99 => 0,
-            x => return err!("{}", format!("Cannot read opcode: {}", x))
-        };
-
-        digit_list.resize(params_length, 0);
-        let parameters: Result<Vec<Parameter>> = digit_list.into_iter().map(|d| match d {
-            0 => Ok(Parameter::Position),
-            1 => Ok(Parameter::Immediate),
-            2 => Ok(Parameter::Relative),
-            x => err!("{}", format!("Cannot read parameter digit: {}", x))
-        }).collect();
-        let parameters = parameters?;
-
-        Ok(
-            Instruction {
-                opcode,
-                parameters,
-            }
-        )
-
-    }
-}
-
-struct Program {
-    memory: Vec<i64>,
-    input: i64,
-    current_input: usize,
-    pointer_idx: usize,
-    relative_base: i64
-}
-
-impl Program {
-    fn new(memory: Vec<i64>)  -> Program {
-        Program {
-            memory,
-            input: 0,
-            current_input: 1,
-            pointer_idx: 0,
-            relative_base: 0
-        }
-    }
-
-    fn get_input(&mut self) -> Result<i64> {
-        Ok(self.input)
-    }
-
-    fn set_input(&mut self, input: i64) {
-        self.input = input;
-    }
-
-    fn get_parameter(&mut self, parameter_form: Parameter, val: i64) -> i64 {
-        use self::Parameter::*;
-
-        match parameter_form {
-            Position => {
-                let idx = val as usize;
-                if self.memory.len() < idx+1 {
-                    self.memory.resize(idx+1, 0);
-                }
-
-                self.memory[idx]
-            },
-            Immediate => val,
-            Relative => {
-                let idx = (self.relative_base + val) as usize;
-                if self.memory.len() < idx+1 {
-                    self.memory.resize(idx+1, 0);
-                }
-
-                self.memory[idx]
-            }
-        }
-    }
-
-    fn set_parameter(&mut self, idx: usize, val: i64) -> Result<()> {
-        if self.memory.len() < idx+1 {
-            self.memory.resize(idx+1, 0);
-        }
-
-        self.memory[idx] = val;
-
-        Ok(())
-    }
-
-    fn get_output_idx(&mut self, idx: usize, parameter_type: Parameter) -> usize {
-        use self::Parameter::*;
-        if self.memory.len() < idx+1 {
-            self.memory.resize(idx+1, 0);
-        }
-        match parameter_type {
-            Position => {
-                self.memory[idx] as usize
-            },
-            Relative => {
-                (self.memory[idx] + self.relative_base) as usize
-            },
-            _ => panic!("Should never be here")
-        }
-    }
-
-    fn run_program(&mut self) -> Result<Option<i64>> {
-        loop {
-            let current_instruction = Instruction::new(self.memory[self.pointer_idx] as usize)?;
-
-            match current_instruction.opcode {
-                1 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, input_1 + input_2)?;
-
-                    self.pointer_idx += 4;
-                },
-                2 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, input_1 * input_2)?;
-
-                    self.pointer_idx += 4;
-                },
-                3 => {
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 1,
-                        current_instruction.parameters[0]
-                    );
-                    let input = self.get_input()?;
-                    self.set_parameter(output_idx, input)?;
-
-                    self.pointer_idx += 2;
-                },
-                4 => {
-                    let output_val = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1]
-                    );
-
-                    // let output_idx = self.memory[self.pointer_idx+1];
-                    self.pointer_idx += 2;
-
-                    return Ok(Some(output_val));
-                },
-                5 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    if input_1 != 0 {
-                        self.pointer_idx = input_2 as usize;
-                    } else {
-                        self.pointer_idx += 3;
-                    }
-                },
-                6 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    if input_1 == 0 {
-                        self.pointer_idx = input_2 as usize;
-                    } else {
-                        self.pointer_idx += 3;
-                    }
-                },
-                7 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, if input_1 < input_2 {1} else {0})?;
-
-                    self.pointer_idx += 4;
-                },
-                8 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, if input_1 == input_2 {1} else {0})?;
-
-                    self.pointer_idx += 4;
-                },
-                9 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    self.relative_base += input_1;
-
-                    self.pointer_idx += 2;
-                },
-                99 => break,
-                x => return err!("{}", format!("Incorrect opcode: {}", x))
-            }
-        }
-        Ok(None)
-    }
-}
-
 #[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
 enum Colour {
     Black,
@@ -389,6 +112,27 @@ impl Colour {
     }
 }
 
+impl TryFrom<i64> for Colour {
+    type Error = Box<dyn Error>;
+
+    fn try_from(n: i64) -> Result<Colour> {
+        Colour::new(n)
+    }
+}
+
+// The way Display draws it, or # for white if a plain ASCII grid is easier to write
+impl FromStr for Colour {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Colour> {
+        match s {
+            "." => Ok(Colour::Black),
+            "█" | "#" => Ok(Colour::White),
+            x => err!("Cannot read colour: {:?} should be one of . █ #", x)
+        }
+    }
+}
+
 impl fmt::Display for Colour {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::Colour::*;
@@ -439,36 +183,19 @@ impl Direction {
     }
 }
 
-fn print_paint_grid(mut paint_grid: BTreeMap<Coordinate, Colour>) {
-    // fill in blanks
-    let mut coords: Vec<Coordinate> = paint_grid.keys().map(|&c| c.clone()).collect();
-    coords.sort();
-    let first_coord = coords.first().unwrap();
-    let last_coord = coords.last().unwrap();
-
-    println!("First coord is {}", first_coord);
-    println!("Last coord is {}", last_coord);
-
-    for y in first_coord.y .. last_coord.y+1 {
-        for x in first_coord.x .. last_coord.x+1 {
-            let coord = Coordinate::new(x, y);
-            if !paint_grid.contains_key(&coord) {
-                paint_grid.insert(coord, Colour::Black);
-            }
-        }
-    }
-
-    for y in first_coord.y .. last_coord.y+1 {
-        for x in first_coord.x .. last_coord.x+1 {
-            let coord = Coordinate::new(x, y);
-            print!("{}", paint_grid.get(&coord).unwrap_or(&Colour::Black));
-            // if !paint_grid.contains_key(&coord) {
-            //     paint_grid.insert(coord, Colour::Black);
-            // }
-        }
-        print!("{}", '\n');
+// Crops to the painted panels, with up (increasing y) at the top. White panels are █, and
+// black or unpainted ones are spaces so the letters stand out.
+fn render_paint_grid(paint_grid: &BTreeMap<Coordinate, Colour>) -> String {
+    let mut tile_map: TileMap<char> = TileMap::new(' ', true);
+    for (coord, &colour) in paint_grid.iter() {
+        let glyph = match colour {
+            Colour::Black => ' ',
+            Colour::White => '█'
+        };
+        tile_map.insert(coord.x as i64, coord.y as i64, glyph);
     }
 
+    tile_map.to_string()
 }
 
 pub fn q1(fname: String) -> usize {
@@ -488,19 +215,19 @@ fn _q1(memory: Vec<i64>) -> Result<usize> {
     let mut current_coord: Coordinate = Coordinate::new(0, 0);
     let mut current_orientation: Direction = Direction::Up;
     loop {
-        program.set_input(
+        program.add_input(
             paint_grid.get(&current_coord)
                 .map(|colour| colour.to_digit())
                 .unwrap_or(0)
         );
 
         // First output: what colour to paint current square
-        if let Some(output) = program.run_program()? {
+        if let Some(output) = program.run_until_output()? {
             paint_grid.insert(current_coord, Colour::new(output)?);
         } else { break; }
 
         // Second output: which direction to move
-        if let Some(output) = program.run_program()? {
+        if let Some(output) = program.run_until_output()? {
             match output {
                 0 => {
                     current_orientation = current_orientation.turn_left();
@@ -539,19 +266,19 @@ fn _q2(memory: Vec<i64>) -> Result<String> {
     paint_grid.insert(current_coord, Colour::White);
 
     loop {
-        program.set_input(
+        program.add_input(
             paint_grid.get(&current_coord)
                 .map(|colour| colour.to_digit())
                 .unwrap_or(0)
         );
 
         // First output: what colour to paint current square
-        if let Some(output) = program.run_program()? {
+        if let Some(output) = program.run_until_output()? {
             paint_grid.insert(current_coord, Colour::new(output)?);
         } else { break; }
 
         // Second output: which direction to move
-        if let Some(output) = program.run_program()? {
+        if let Some(output) = program.run_until_output()? {
             match output {
                 0 => {
                     current_orientation = current_orientation.turn_left();
@@ -566,8 +293,81 @@ fn _q2(memory: Vec<i64>) -> Result<String> {
         } else { break; }
     }
 
-    // fill in blanks with black squares
-    print_paint_grid(paint_grid);
+    let registration = render_paint_grid(&paint_grid);
+    if debug::verbose() {
+        eprint!("{}", registration);
+    }
+
+    Ok(registration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok("".to_string())
-}
\ No newline at end of file
+    #[test]
+    fn day11_render_crop_test() {
+        // paints the starting panel black, turns left and moves to (-1, 0), paints that white
+        let memory = vec![3, 100, 104, 0, 104, 0, 3, 100, 104, 1, 104, 0, 99];
+
+        assert_eq!(
+            _q2(memory).unwrap(),
+            "█ \n"
+        );
+
+        let mut paint_grid: BTreeMap<Coordinate, Colour> = BTreeMap::new();
+        paint_grid.insert(Coordinate::new(-3, -2), Colour::White);
+        assert_eq!(render_paint_grid(&paint_grid), "█\n");
+    }
+
+    #[test]
+    fn day11_q2_rows_test() {
+        // paints white while going up and to the left in steps: turn left, move, turn right, move
+        let mut memory = vec![];
+        for _ in 0..5 {
+            memory.extend(vec![3, 1000, 104, 1, 104, 0, 3, 1000, 104, 1, 104, 1]);
+        }
+        memory.extend(vec![3, 1000, 104, 1, 104, 0, 99]);
+
+        let registration = _q2(memory).unwrap();
+        let rows: Vec<&str> = registration.lines().collect();
+
+        assert!(!registration.is_empty());
+        assert_eq!(rows.len(), 6);
+        assert!(rows.iter().all(|row| row.contains('█')));
+        assert_eq!(rows[0], "█     ");
+    }
+
+    #[test]
+    fn day11_render_negative_test() {
+        let mut paint_grid: BTreeMap<Coordinate, Colour> = BTreeMap::new();
+        paint_grid.insert(Coordinate::new(-2, 1), Colour::White);
+        paint_grid.insert(Coordinate::new(0, 0), Colour::White);
+        paint_grid.insert(Coordinate::new(0, -1), Colour::Black);
+        paint_grid.insert(Coordinate::new(1, -2), Colour::White);
+
+        // y = 1 at the top, x = -2 on the left
+        assert_eq!(
+            render_paint_grid(&paint_grid),
+            "█   \n  █ \n    \n   █\n"
+        );
+    }
+
+    #[test]
+    fn day11_parse_colour_test() {
+        assert_eq!(Colour::try_from(0).unwrap(), Colour::Black);
+        assert_eq!(Colour::try_from(1).unwrap(), Colour::White);
+        assert_eq!(Colour::try_from(2).unwrap_err().to_string(), "Invalid colour: 2");
+
+        assert_eq!(".".parse::<Colour>().unwrap(), Colour::Black);
+        assert_eq!("█".parse::<Colour>().unwrap(), Colour::White);
+        assert_eq!("#".parse::<Colour>().unwrap(), Colour::White);
+        for colour in [Colour::Black, Colour::White].iter() {
+            assert_eq!(colour.to_string().parse::<Colour>().unwrap(), *colour);
+        }
+        assert_eq!(
+            "##".parse::<Colour>().unwrap_err().to_string(),
+            "Cannot read colour: \"##\" should be one of . █ #"
+        );
+    }
+}
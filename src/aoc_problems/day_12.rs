@@ -2,7 +2,6 @@ use std::cmp;
 use std::error::Error;
 use std::fs::File;
 use std::fmt;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
@@ -10,24 +9,15 @@ use std::str::FromStr;
 
 use regex::Regex;
 
+use debug;
+use math::lcm;
+
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
 macro_rules! err {
     ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
-
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
 struct Coordinate {
     x: i32,
@@ -72,7 +62,7 @@ impl Sub for Coordinate {
         Coordinate {
             x: self.x - other.x,
             y: self.y - other.y,
-            z: self.y - other.z,
+            z: self.z - other.z,
         }
     }
 }
@@ -131,6 +121,31 @@ impl fmt::Display for Coordinate {
     }
 }
 
+// A moon in however many dimensions the simulation is using
+trait Body: Clone {
+    // changes velocity by the pull from other
+    fn apply_gravity_from(&mut self, other: &Self);
+
+    // changes position by velocity
+    fn apply_velocity(&mut self);
+}
+
+// One time step: every moon's velocity is updated from all the others' positions first,
+// and only then do the positions move
+fn step_moons<B: Body>(moons: &mut [B]) {
+    let other_moons = moons.to_vec();
+
+    for moon in moons.iter_mut() {
+        for other_moon in &other_moons {
+            moon.apply_gravity_from(other_moon);
+        }
+    }
+
+    for moon in moons.iter_mut() {
+        moon.apply_velocity();
+    }
+}
+
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
 struct Moon1D {
     position: i32,
@@ -150,6 +165,16 @@ impl Moon1D {
     }
 }
 
+impl Body for Moon1D {
+    fn apply_gravity_from(&mut self, other: &Moon1D) {
+        self.velocity += self.gravity_from(other);
+    }
+
+    fn apply_velocity(&mut self) {
+        self.position += self.velocity;
+    }
+}
+
 impl fmt::Display for Moon1D {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Pos: {}, velocity: {}", self.position, self.velocity)
@@ -167,28 +192,8 @@ impl Jupiter1D {
         }
     }
 
-    fn add_gravity_effect(&mut self) -> Result<()> {
-        let other_moons = self.moons.clone();
-
-        for moon in &mut self.moons {
-            for other_moon in &other_moons {
-                moon.velocity += moon.gravity_from(&other_moon);
-            }
-        }
-        Ok(())
-    }
-
-    fn move_moons(&mut self) -> Result<()> {
-        for moon in &mut self.moons {
-            moon.position += moon.velocity;
-        }
-
-        Ok(())
-    }
-
     fn increment_time(&mut self) -> Result<()> {
-        self.add_gravity_effect()?;
-        self.move_moons()?;
+        step_moons(&mut self.moons);
 
         Ok(())
     }
@@ -256,12 +261,23 @@ impl Moon {
     }
 }
 
+impl Body for Moon {
+    fn apply_gravity_from(&mut self, other: &Moon) {
+        self.velocity += self.gravity_from(other);
+    }
+
+    fn apply_velocity(&mut self) {
+        self.position += self.velocity;
+    }
+}
+
 impl fmt::Display for Moon {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Pos: {}, velocity: {}", self.position, self.velocity)
     }
 }
 
+#[derive(Clone)]
 struct Jupiter {
     moons: Vec<Moon>
 }
@@ -273,34 +289,36 @@ impl Jupiter {
         }
     }
 
-    fn add_gravity_effect(&mut self) -> Result<()> {
-        let other_moons = self.moons.clone();
+    fn increment_time(&mut self) -> Result<()> {
+        step_moons(&mut self.moons);
+
+        // gravity is pairwise symmetric, so momentum is always conserved
+        debug_assert_eq!(self.total_momentum(), Coordinate::default());
 
-        for moon in &mut self.moons {
-            for other_moon in &other_moons {
-                moon.velocity += moon.gravity_from(&other_moon);
-            }
-        }
         Ok(())
     }
 
-    fn move_moons(&mut self) -> Result<()> {
-        for moon in &mut self.moons {
-            moon.position += moon.velocity;
-        }
-
-        Ok(())
+    fn total_energy(&self) -> usize {
+        self.moons.iter().map(|moon| moon.total_energy()).sum()
     }
 
-    fn increment_time(&mut self) -> Result<()> {
-        self.add_gravity_effect()?;
-        self.move_moons()?;
+    // Total energy after the given number of steps, leaving this Jupiter where it is
+    fn simulate(&self, steps: usize) -> Result<usize> {
+        let mut jupiter = self.clone();
+        for _ in 0..steps {
+            jupiter.increment_time()?;
+        }
 
-        Ok(())
+        Ok(jupiter.total_energy())
     }
 
-    fn total_energy(&self) -> usize {
-        self.moons.iter().map(|moon| moon.total_energy()).sum()
+    fn total_momentum(&self) -> Coordinate {
+        let mut momentum = Coordinate::default();
+        for moon in &self.moons {
+            momentum += moon.velocity;
+        }
+
+        momentum
     }
 }
 
@@ -313,18 +331,6 @@ impl fmt::Display for Jupiter {
     }
 }
 
-fn gcd(m: usize, n: usize) -> usize {
-    if m == 0 {
-        n
-    } else {
-        gcd(n % m, m)
-    }
-}
-
-fn lcm(m: usize, n: usize) -> usize {
-    m * n / gcd(m, n)
-}
-
 pub fn q1(fname: String) -> usize {
     let mut f = File::open(fname).expect("File not found");
     let mut f_contents = String::new();
@@ -336,7 +342,7 @@ pub fn q1(fname: String) -> usize {
     match _q1(coords, 1000) {
         Ok(result) => result,
         Err(e) => {
-            println!("Error: {}", e);
+            eprintln!("Error: {}", e);
             panic!();
         }
     }
@@ -352,13 +358,9 @@ fn _q1(coords: Vec<String>, t: usize) -> Result<usize> {
     let moons: Result<Vec<Coordinate>> = coords.iter().map(|line| line.parse()).collect();
     let moons = moons?;
 
-    let mut jupiter = Jupiter::new(moons);
+    let jupiter = Jupiter::new(moons);
 
-    for _ in 0..t {
-        jupiter.increment_time()?;
-    }
-
-    Ok(jupiter.total_energy())
+    jupiter.simulate(t)
 }
 
 pub fn q2(fname: String) -> usize {
@@ -372,7 +374,7 @@ pub fn q2(fname: String) -> usize {
     match _q2(coords) {
         Ok(result) => result,
         Err(e) => {
-            println!("Error: {}", e);
+            eprintln!("Error: {}", e);
             panic!();
         }
     }
@@ -390,11 +392,17 @@ fn _q2(coords: Vec<String>) -> Result<usize> {
     let initial_z: Vec<i32> = moons.iter().map(|&moon| moon.z).collect();
 
     let x_period = period_1d(initial_x)?;
-    println!("x period is {}", x_period);
+    if debug::verbose() {
+        eprintln!("x period is {}", x_period);
+    }
     let y_period = period_1d(initial_y)?;
-    println!("y period is {}", y_period);
+    if debug::verbose() {
+        eprintln!("y period is {}", y_period);
+    }
     let z_period = period_1d(initial_z)?;
-    println!("z period is {}", z_period);
+    if debug::verbose() {
+        eprintln!("z period is {}", z_period);
+    }
 
     Ok(lcm(x_period, lcm(y_period, z_period)))
 }
@@ -462,4 +470,84 @@ mod tests {
             4686774924
         )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn day12_momentum_test() {
+        let moon_coords: Result<Vec<Coordinate>> = "
+            <x=-8, y=-10, z=0>
+            <x=5, y=5, z=10>
+            <x=2, y=-7, z=3>
+            <x=9, y=-8, z=-3>
+        ".trim().lines().map(|line| line.trim().parse()).collect();
+
+        let mut jupiter = Jupiter::new(moon_coords.unwrap());
+        for _ in 0..100 {
+            jupiter.increment_time().unwrap();
+            assert_eq!(jupiter.total_momentum(), Coordinate::new(0, 0, 0));
+        }
+    }
+
+    #[test]
+    fn day12_simulate_test() {
+        let moon_coords: Result<Vec<Coordinate>> = "
+            <x=-1, y=0, z=2>
+            <x=2, y=-10, z=-7>
+            <x=4, y=-8, z=8>
+            <x=3, y=5, z=-1>
+        ".trim().lines().map(|line| line.trim().parse()).collect();
+
+        let jupiter = Jupiter::new(moon_coords.unwrap());
+
+        assert_eq!(jupiter.simulate(10).unwrap(), 179);
+        assert_eq!(jupiter.simulate(100).unwrap(), 293);
+        assert_eq!(jupiter.simulate(10).unwrap(), 179);
+        assert_eq!(jupiter.total_energy(), 0);
+    }
+
+    #[test]
+    fn day12_single_step_test() {
+        let moon_coords: Result<Vec<Coordinate>> = "
+            <x=-1, y=0, z=2>
+            <x=2, y=-10, z=-7>
+            <x=4, y=-8, z=8>
+            <x=3, y=5, z=-1>
+        ".trim().lines().map(|line| line.trim().parse()).collect();
+        let moon_coords = moon_coords.unwrap();
+
+        // the new velocity is already included in the new position
+        let mut jupiter = Jupiter::new(moon_coords.clone());
+        jupiter.increment_time().unwrap();
+        assert_eq!(jupiter.moons[0].velocity, Coordinate::new(3, -1, -1));
+        assert_eq!(jupiter.moons[0].position, Coordinate::new(2, -1, 1));
+        assert_eq!(jupiter.moons[1].velocity, Coordinate::new(1, 3, 3));
+        assert_eq!(jupiter.moons[1].position, Coordinate::new(3, -7, -4));
+
+        // and the same along just x
+        let mut jupiter_1d = Jupiter1D::new(moon_coords.iter().map(|coord| coord.x).collect());
+        jupiter_1d.increment_time().unwrap();
+        assert_eq!(
+            jupiter_1d.moons.iter().map(|moon| moon.velocity).collect::<Vec<i32>>(),
+            vec![3, 1, -3, -1]
+        );
+        assert_eq!(
+            jupiter_1d.moons.iter().map(|moon| moon.position).collect::<Vec<i32>>(),
+            vec![2, 3, 1, 2]
+        );
+    }
+
+    #[test]
+    fn day12_coordinate_sub_test() {
+        assert_eq!(Coordinate::new(1, 2, 3) - Coordinate::new(1, 1, 1), Coordinate::new(0, 1, 2));
+
+        // adding and subtracting the same thing gets back to where it started
+        let values = [-7, -1, 0, 2, 13];
+        for &a in values.iter() {
+            for &b in values.iter() {
+                let first = Coordinate::new(a, b, a - b);
+                let second = Coordinate::new(b, a * 2, -a);
+                assert_eq!((first + second) - second, first);
+                assert_eq!((first - second) + second, first);
+            }
+        }
+    }
+}
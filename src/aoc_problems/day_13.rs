@@ -1,32 +1,24 @@
 use std::cmp;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fs::File;
 use std::fmt;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
+use std::str::FromStr;
 
 use std::collections::BTreeMap;
 
+use intcode::{Program, StepResult};
+use tile_map::TileMap;
+
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
 macro_rules! err {
     ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
-
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
 struct Coordinate {
     x: usize,
@@ -94,299 +86,54 @@ impl fmt::Display for Coordinate {
     }
 }
 
-#[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
-enum Parameter {
-    Position,
-    Immediate,
-    Relative
-}
-
-#[derive(Clone, Eq, Default, Debug, PartialEq, Hash)]
-struct Instruction {
-    opcode: usize,
-    parameters: Vec<Parameter>
-}
-
-impl Instruction {
-    fn new(number: usize) -> Result<Instruction> {
-        let opcode = number % 100;
-        let mut digit_list: Vec<_> = (number / 100).to_string().chars().map(|d| d.to_digit(10).unwrap()).collect();
-        digit_list.reverse();
-
-        let params_length = match opcode {
-            1 => 3,
-            2 => 3,
-            3 => 1,
-            4 => 1,
-            5 => 2,
-            6 => 2,
-            7 => 3,
-            8 => 3,
-            9 => 1,
-            99 => 0,
-            x => return err!("{}", format!("Cannot read opcode: {}", x))
-        };
-
-        digit_list.resize(params_length, 0);
-        let parameters: Result<Vec<Parameter>> = digit_list.into_iter().map(|d| match d {
-            0 => Ok(Parameter::Position),
-            1 => Ok(Parameter::Immediate),
-            2 => Ok(Parameter::Relative),
-            x => err!("{}", format!("Cannot read parameter digit: {}", x))
-        }).collect();
-        let parameters = parameters?;
-
-        Ok(
-            Instruction {
-                opcode,
-                parameters,
-            }
-        )
-
-    }
-}
-
-struct Program {
-    memory: Vec<i64>,
-    input: i64,
-    current_input: usize,
-    pointer_idx: usize,
-    relative_base: i64,
+struct Arcade {
+    program: Program,
     display: BTreeMap<Coordinate, TileType>,
     score: usize,
-    ball_coord: Coordinate,
-    paddle_coord: Coordinate
+    // where the ball and the paddle were last drawn, None until the game has drawn them
+    ball_x: Option<usize>,
+    paddle_x: Option<usize>
 }
 
-impl Program {
-    fn new(memory: Vec<i64>)  -> Program {
-        Program {
-            memory,
-            input: 0,
-            current_input: 1,
-            pointer_idx: 0,
-            relative_base: 0,
+impl Arcade {
+    fn new(memory: Vec<i64>) -> Arcade {
+        Arcade {
+            program: Program::new(memory),
             display: BTreeMap::new(),
             score: 0,
-            ball_coord: Coordinate::new(0, 0),
-            paddle_coord: Coordinate::new(0, 0),
+            ball_x: None,
+            paddle_x: None
         }
     }
 
-    fn get_input(&mut self) -> Result<i64> {
-        Ok((self.ball_coord.x as i64 - self.paddle_coord.x as i64).signum())
-    }
-
-    fn set_input(&mut self, input: i64) {
-        self.input = input;
-    }
-
-    fn get_parameter(&mut self, parameter_form: Parameter, val: i64) -> i64 {
-        use self::Parameter::*;
-
-        match parameter_form {
-            Position => {
-                let idx = val as usize;
-                if self.memory.len() < idx+1 {
-                    self.memory.resize(idx+1, 0);
-                }
-
-                self.memory[idx]
-            },
-            Immediate => val,
-            Relative => {
-                let idx = (self.relative_base + val) as usize;
-                if self.memory.len() < idx+1 {
-                    self.memory.resize(idx+1, 0);
-                }
-
-                self.memory[idx]
-            }
+    // Moves the paddle towards the ball
+    fn joystick(&self) -> i64 {
+        match (self.ball_x, self.paddle_x) {
+            (Some(ball_x), Some(paddle_x)) => (ball_x as i64 - paddle_x as i64).signum(),
+            // nothing to aim at yet, so keep the paddle where it is
+            _ => 0
         }
     }
 
-    fn set_parameter(&mut self, idx: usize, val: i64) -> Result<()> {
-        if self.memory.len() < idx+1 {
-            self.memory.resize(idx+1, 0);
-        }
-
-        self.memory[idx] = val;
-
-        Ok(())
-    }
-
-    fn get_output_idx(&mut self, idx: usize, parameter_type: Parameter) -> usize {
-        use self::Parameter::*;
-        if self.memory.len() < idx+1 {
-            self.memory.resize(idx+1, 0);
-        }
-        match parameter_type {
-            Position => {
-                self.memory[idx] as usize
-            },
-            Relative => {
-                (self.memory[idx] + self.relative_base) as usize
-            },
-            _ => panic!("Should never be here")
-        }
-    }
-
-    fn run_program(&mut self) -> Result<Option<i64>> {
+    fn run_game(&mut self) -> Result<()> {
+        // x, y then tile type
+        let mut outputs: Vec<i64> = vec![];
         loop {
-            let current_instruction = Instruction::new(self.memory[self.pointer_idx] as usize)?;
-
-            match current_instruction.opcode {
-                1 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, input_1 + input_2)?;
-
-                    self.pointer_idx += 4;
-                },
-                2 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, input_1 * input_2)?;
-
-                    self.pointer_idx += 4;
-                },
-                3 => {
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 1,
-                        current_instruction.parameters[0]
-                    );
-                    let input = self.get_input()?;
-                    self.set_parameter(output_idx, input)?;
-
-                    self.pointer_idx += 2;
-                },
-                4 => {
-                    let output_val = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1]
-                    );
-
-                    // let output_idx = self.memory[self.pointer_idx+1];
-                    self.pointer_idx += 2;
-
-                    return Ok(Some(output_val));
-                },
-                5 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    if input_1 != 0 {
-                        self.pointer_idx = input_2 as usize;
-                    } else {
-                        self.pointer_idx += 3;
-                    }
-                },
-                6 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    if input_1 == 0 {
-                        self.pointer_idx = input_2 as usize;
-                    } else {
-                        self.pointer_idx += 3;
-                    }
-                },
-                7 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, if input_1 < input_2 {1} else {0})?;
-
-                    self.pointer_idx += 4;
-                },
-                8 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, if input_1 == input_2 {1} else {0})?;
-
-                    self.pointer_idx += 4;
+            match self.program.step_until_event()? {
+                StepResult::NeedInput => {
+                    let input = self.joystick();
+                    self.program.add_input(input);
+                    continue;
                 },
-                9 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    self.relative_base += input_1;
-
-                    self.pointer_idx += 2;
-                },
-                99 => break,
-                x => return err!("{}", format!("Incorrect opcode: {}", x))
+                StepResult::Output(output) => outputs.push(output),
+                StepResult::Halted => break
             }
-        }
-        Ok(None)
-    }
-
-    fn run_game(&mut self) -> Result<()> {
-        loop {
-            // First output: x coord
-            let x = if let Some(output) = self.run_program()? {
-                output
-            } else { break; };
 
-            // Second output: y coord
-            let y = if let Some(output) = self.run_program()? {
-                output
-            } else { break; };
-
-            // Third output: tile type
-            let third_output = if let Some(output) = self.run_program()? {
-                output
-            } else { break; };
+            if outputs.len() < 3 {
+                continue;
+            }
+            let (x, y, third_output) = (outputs[0], outputs[1], outputs[2]);
+            outputs.clear();
 
             match (x, y) {
                 (-1, 0) => {
@@ -397,11 +144,11 @@ impl Program {
                     let tile = TileType::new(third_output as usize)?;
 
                     if tile == TileType::Ball {
-                        self.ball_coord = coord;
+                        self.ball_x = Some(coord.x);
                     }
 
                     if tile == TileType::Paddle {
-                        self.paddle_coord = coord;
+                        self.paddle_x = Some(coord.x);
                     }
                     self.display.insert(coord, tile);
 
@@ -418,18 +165,43 @@ impl Program {
     }
 }
 
-impl fmt::Display for Program {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let current_y: usize = 0;
+impl Arcade {
+    // The screen drawn with the given glyphs instead of the usual ones
+    fn render_with(&self, glyphs: &TileGlyphs) -> String {
+        let mut tile_map: TileMap<char> = TileMap::new(' ', false);
         for (coord, tile) in self.display.iter() {
-            if coord.y != current_y {
-                write!(f, "{}", '\n')?;
-            }
-
-            write!(f, "{}", tile)?;
+            tile_map.insert(coord.x as i64, coord.y as i64, tile.glyph(glyphs));
         }
 
-        Ok(())
+        tile_map.to_string()
+    }
+}
+
+impl fmt::Display for Arcade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render_with(&TileGlyphs::default()))
+    }
+}
+
+// The character each tile is drawn with
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct TileGlyphs {
+    empty: char,
+    wall: char,
+    block: char,
+    paddle: char,
+    ball: char
+}
+
+impl Default for TileGlyphs {
+    fn default() -> TileGlyphs {
+        TileGlyphs {
+            empty: '.',
+            wall: '█',
+            block: 'x',
+            paddle: '-',
+            ball: 'O'
+        }
     }
 }
 
@@ -451,7 +223,7 @@ impl TileType {
             2 => Ok(Block),
             3 => Ok(Paddle),
             4 => Ok(Ball),
-            x => err!("Invalid colour: {}", x)
+            x => err!("Invalid tile type: {}", x)
         }
     }
 
@@ -467,19 +239,50 @@ impl TileType {
     }
 }
 
-impl fmt::Display for TileType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl TileType {
+    fn glyph(&self, glyphs: &TileGlyphs) -> char {
         use self::TileType::*;
         match self {
-            Empty => write!(f, "."),
-            Wall => write!(f, "█"),
-            Block => write!(f, "x"),
-            Paddle => write!(f, "-"),
-            Ball => write!(f, "O"),
+            Empty => glyphs.empty,
+            Wall => glyphs.wall,
+            Block => glyphs.block,
+            Paddle => glyphs.paddle,
+            Ball => glyphs.ball,
         }
     }
 }
 
+impl TryFrom<i64> for TileType {
+    type Error = Box<dyn Error>;
+
+    fn try_from(n: i64) -> Result<TileType> {
+        if n < 0 {
+            return err!("Invalid tile type: {}", n);
+        }
+
+        TileType::new(n as usize)
+    }
+}
+
+// A single glyph as Display draws it
+impl FromStr for TileType {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<TileType> {
+        use self::TileType::*;
+        [Empty, Wall, Block, Paddle, Ball].iter()
+            .find(|tile| tile.to_string() == s)
+            .cloned()
+            .ok_or_else(|| format!("Cannot read tile: {:?} isn't one of . █ x - O", s).into())
+    }
+}
+
+impl fmt::Display for TileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.glyph(&TileGlyphs::default()))
+    }
+}
+
 
 pub fn q1(fname: String) -> usize {
     let mut f = File::open(fname).expect("File not found");
@@ -497,23 +300,31 @@ fn _q1(memory: Vec<i64>) -> Result<usize> {
     let mut tiles: BTreeMap<Coordinate, TileType> = BTreeMap::new();
     loop {
         // First output: x coord
-        let x: usize = if let Some(output) = program.run_program()? {
+        let x: usize = if let Some(output) = program.run_until_output()? {
             output as usize
         } else { break; };
 
         // Second output: y coord
-        let y: usize = if let Some(output) = program.run_program()? {
+        let y: usize = if let Some(output) = program.run_until_output()? {
             output as usize
         } else { break; };
 
         // Third output: tile type
-        let tile_type: usize = if let Some(output) = program.run_program()? {
+        let tile_type: usize = if let Some(output) = program.run_until_output()? {
             output as usize
         } else { break; };
 
         tiles.insert(Coordinate::new(x, y), TileType::new(tile_type)?);
     }
 
+    // halting part way through a tile would otherwise lose it without anyone noticing
+    if !program.output_count().is_multiple_of(3) {
+        return err!(
+            "Program halted part way through a tile: {} outputs isn't a multiple of 3",
+            program.output_count()
+        );
+    }
+
     Ok(tiles.values().filter(|&&tile| tile == TileType::Block).count())
 }
 
@@ -532,8 +343,88 @@ fn _q2(mut memory: Vec<i64>) -> Result<usize> {
     // Change first number so game can play
     memory[0] = 2;
 
-    let mut program = Program::new(memory);
-    program.run_game()?;
+    let mut arcade = Arcade::new(memory);
+    arcade.run_game()?;
+
+    Ok(arcade.score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day13_output_triples_test() {
+        // two blocks and an empty tile
+        let memory = vec![104, 1, 104, 2, 104, 2, 104, 3, 104, 2, 104, 2, 104, 0, 104, 0, 104, 0, 99];
+        assert_eq!(_q1(memory.clone()).unwrap(), 2);
+
+        // a stray extra output at the end
+        let mut partial_memory = memory;
+        partial_memory.pop();
+        partial_memory.extend(vec![104, 5, 99]);
+        match _q1(partial_memory) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Program halted part way through a tile: 10 outputs isn't a multiple of 3"
+            ),
+            Ok(_) => panic!("A partial tile should be an error")
+        }
+    }
 
-    Ok(program.score)
-}
\ No newline at end of file
+    #[test]
+    fn day13_joystick_before_drawing_test() {
+        // Reads the joystick before anything is drawn, after only the ball is drawn and then
+        // after the paddle is drawn too, and reports the three readings (each plus one) as the
+        // digits of the score
+        let memory = vec![
+            3, 200,
+            104, 5, 104, 1, 104, 4,
+            3, 201,
+            104, 3, 104, 2, 104, 3,
+            3, 202,
+            1001, 200, 1, 200, 1002, 200, 100, 200,
+            1001, 201, 1, 201, 1002, 201, 10, 201,
+            1001, 202, 1, 202,
+            1, 200, 201, 203, 1, 203, 202, 203,
+            104, -1, 104, 0, 4, 203,
+            99
+        ];
+
+        let mut arcade = Arcade::new(memory);
+        arcade.run_game().unwrap();
+
+        // neutral until both the ball and the paddle have been seen, then towards the ball
+        assert_eq!(arcade.score, 112);
+    }
+
+    #[test]
+    fn day13_render_with_test() {
+        let mut arcade = Arcade::new(vec![99]);
+        for (x, tile) in [TileType::Wall, TileType::Block, TileType::Empty, TileType::Wall].iter().enumerate() {
+            arcade.display.insert(Coordinate::new(x, 0), *tile);
+        }
+        arcade.display.insert(Coordinate::new(1, 1), TileType::Ball);
+        arcade.display.insert(Coordinate::new(2, 2), TileType::Paddle);
+
+        assert_eq!(arcade.to_string(), "█x.█\n O  \n  - \n");
+
+        let ascii = TileGlyphs { empty: ' ', wall: '#', block: '=', paddle: '_', ball: '*' };
+        assert_eq!(arcade.render_with(&ascii), "#= #\n *  \n  _ \n");
+    }
+
+    #[test]
+    fn day13_parse_tile_test() {
+        assert_eq!(TileType::try_from(2).unwrap(), TileType::Block);
+        assert_eq!(TileType::try_from(4).unwrap(), TileType::Ball);
+        assert_eq!(TileType::try_from(5).unwrap_err().to_string(), "Invalid tile type: 5");
+        assert_eq!(TileType::try_from(-1).unwrap_err().to_string(), "Invalid tile type: -1");
+
+        assert_eq!("█".parse::<TileType>().unwrap(), TileType::Wall);
+        assert_eq!("-".parse::<TileType>().unwrap(), TileType::Paddle);
+        assert_eq!(
+            "#".parse::<TileType>().unwrap_err().to_string(),
+            "Cannot read tile: \"#\" isn't one of . █ x - O"
+        );
+    }
+}
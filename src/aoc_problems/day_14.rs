@@ -1,32 +1,23 @@
+use std::cmp;
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 use std::result;
 use std::str::FromStr;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use regex::Regex;
 
+use debug;
+
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
 macro_rules! err {
     ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
-
 #[derive(Clone, Default, Debug, Eq, PartialEq, Hash)]
 struct Material {
     chemical: String,
@@ -78,12 +69,31 @@ struct RecipeRequirements {
     inputs: Vec<Material>
 }
 
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.chemical)
+    }
+}
+
+impl fmt::Display for RecipeRequirements {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inputs: Vec<String> = self.inputs.iter().map(|material| material.to_string()).collect();
+        write!(f, "{} => {}", inputs.join(", "), self.output)
+    }
+}
+
+// The ore in the cargo hold for part 2
+const DEFAULT_ORE_BUDGET: usize = 1_000_000_000_000;
+
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
 struct Nanofactory {
     recipes: HashMap<String, RecipeRequirements>,
     material_counts: HashMap<String, usize>,
     ore_usage: usize,
-    fuel_count: usize
+    ore_budget: usize,
+    fuel_count: usize,
+    // how many times a reaction (any number of batches of one recipe) has been run
+    reactions_run: usize
 }
 
 impl Nanofactory {
@@ -125,22 +135,34 @@ impl Nanofactory {
                 recipes: recipe_map,
                 material_counts,
                 ore_usage: 0,
-                fuel_count: 0
+                ore_budget: DEFAULT_ORE_BUDGET,
+                fuel_count: 0,
+                reactions_run: 0
             }
         )
     }
 
+    fn set_ore_budget(&mut self, ore_budget: usize) {
+        self.ore_budget = ore_budget;
+    }
+
+    // Reactions run since the factory was made or last wiped
+    fn reaction_count(&self) -> usize {
+        self.reactions_run
+    }
+
     fn _create(&mut self, chemical_name: String, minimum_amount: usize) -> Result<()> {
         let chemical_rqmts = self.recipes.get(&chemical_name)
             .ok_or(format!("Cannot find recipe for chemical {}", chemical_name))?
             .clone();
+        self.reactions_run += 1;
 
         let complete_sets_needed: usize = (minimum_amount as f64 / chemical_rqmts.output.amount as f64).ceil() as usize;
 
         for input_material in &chemical_rqmts.inputs {
             if &input_material.chemical == "ORE" {
                 self.ore_usage += complete_sets_needed*input_material.amount;
-                if self.ore_usage > 1_000_000_000_000 {
+                if self.ore_usage > self.ore_budget {
                     return err!("Run out of ore");
                 }
                 continue;
@@ -210,6 +232,41 @@ impl Nanofactory {
     //     Ok(())
     // }
 
+    // Every chemical needed for FUEL, ordered so each comes after all of its inputs (ORE first)
+    fn topological_order(&self) -> Result<Vec<String>> {
+        let mut order: Vec<String> = Vec::new();
+        let mut in_progress: HashSet<String> = HashSet::new();
+
+        self.add_to_order("FUEL", &mut in_progress, &mut order)?;
+
+        Ok(order)
+    }
+
+    fn add_to_order(&self, chemical_name: &str, in_progress: &mut HashSet<String>, order: &mut Vec<String>) -> Result<()> {
+        if order.iter().any(|chemical| chemical == chemical_name) {
+            return Ok(());
+        }
+
+        if chemical_name != "ORE" {
+            if !in_progress.insert(chemical_name.to_string()) {
+                return err!("Recipes contain a cycle through {}", chemical_name);
+            }
+
+            let chemical_rqmts = self.recipes.get(chemical_name)
+                .ok_or(format!("Cannot find recipe for chemical {}", chemical_name))?;
+
+            for input_material in &chemical_rqmts.inputs {
+                self.add_to_order(&input_material.chemical, in_progress, order)?;
+            }
+
+            in_progress.remove(chemical_name);
+        }
+
+        order.push(chemical_name.to_string());
+
+        Ok(())
+    }
+
     fn produce_one_fuel(&mut self) -> Result<()> {
         self._create("FUEL".to_string(), 1)?;
 
@@ -235,41 +292,95 @@ impl Nanofactory {
         Ok((fuel_produced, total_ore_used))
     }
 
+    // Whether amount of chemical can be made from scratch within the ore budget.
+    // Leaves the factory exactly as it was.
+    fn can_produce(&mut self, chemical_name: &str, amount: usize) -> bool {
+        let material_counts = self.material_counts.clone();
+        let ore_usage = self.ore_usage;
+        let reactions_run = self.reactions_run;
+
+        self.wipe_everything();
+        let can_produce = self._create(chemical_name.to_string(), amount).is_ok();
+
+        self.material_counts = material_counts;
+        self.ore_usage = ore_usage;
+        self.reactions_run = reactions_run;
+
+        can_produce
+    }
+
+    // ORE per unit of a chemical, from making one batch of it in a fresh copy of the factory.
+    // Any leftovers from making its inputs count towards the cost.
+    fn ore_cost_of(&self, chemical_name: &str) -> Result<f64> {
+        if chemical_name == "ORE" {
+            return Ok(1.0);
+        }
+
+        let batch_size = self.recipes.get(chemical_name)
+            .ok_or(format!("Cannot find recipe for chemical {}", chemical_name))?
+            .output.amount;
+
+        let mut sub_factory = self.clone();
+        sub_factory.wipe_everything();
+        sub_factory.set_ore_budget(usize::MAX);
+        sub_factory._create(chemical_name.to_string(), batch_size)?;
+
+        Ok(sub_factory.ore_usage as f64 / batch_size as f64)
+    }
+
     fn wipe_everything(&mut self) {
         self.material_counts = self.material_counts.keys().map(|k| (k.clone(), 0)).collect();
         self.ore_usage = 0;
+        self.reactions_run = 0;
     }
 
+    // lower_limit must be an amount of fuel that can definitely be made
     fn max_fuel_output(&mut self, lower_limit: usize) -> Result<usize> {
-        // let mut total_fuel_produced = 0;
-        // answer in [955_000, 955_000 + 1_048_576]
-        // binary search
-
-        // let upper_limit = lower_limit + 1_048_576;
-
-        let mut current_guess = lower_limit;
-        let mut jump = 1048576/2;
-
-        let mut result = 0;
+        let mut can_make = lower_limit;
 
+        // double until we find an amount that can't be made...
+        let mut cannot_make = cmp::max(2 * lower_limit, 1);
         loop {
-            self.wipe_everything();
+            if !self.can_produce("FUEL", cannot_make) {
+                break;
+            }
+            can_make = cannot_make;
+            cannot_make *= 2;
+        }
+
+        // ...then binary search between the two
+        while cannot_make - can_make > 1 {
+            let current_guess = (can_make + cannot_make) / 2;
 
-            if self._create("FUEL".to_string(), current_guess).is_ok() {
-                println!("{} fuel needed {} ore", current_guess, self.ore_usage);
-                result = current_guess;
-                current_guess += jump;
+            if self.can_produce("FUEL", current_guess) {
+                if debug::verbose() {
+                    eprintln!("Can make {} fuel", current_guess);
+                }
+                can_make = current_guess;
             } else {
-                println!("Cannot make {} fuel", current_guess);
-                current_guess -= jump;
+                if debug::verbose() {
+                    eprintln!("Cannot make {} fuel", current_guess);
+                }
+                cannot_make = current_guess;
             }
+        }
 
-            if jump == 0 {
-                return Ok(result);
-            }
+        Ok(can_make)
+    }
 
-            jump /= 2;
+    // Makes FUEL one at a time from scratch, keeping the leftovers from each for the next,
+    // until the next one would need more than ore_budget. Far slower than max_fuel_output,
+    // but simple enough to check it against.
+    fn produce_until_exhausted(&mut self, ore_budget: usize) -> usize {
+        self.wipe_everything();
+        self.set_ore_budget(ore_budget);
+
+        let mut fuel_produced = 0;
+        while self._create("FUEL".to_string(), 1).is_ok() {
+            fuel_produced += 1;
         }
+
+        fuel_produced
     }
 }
 
@@ -307,19 +418,27 @@ pub fn q2(fname: String) -> usize {
 
 
 fn _q2(recipes: Vec<String>) -> Result<usize> {
+    max_fuel_from_ore(recipes, DEFAULT_ORE_BUDGET)
+}
+
+fn max_fuel_from_ore(recipes: Vec<String>, ore_budget: usize) -> Result<usize> {
     let mut nanofactory = Nanofactory::new(recipes)?;
+    nanofactory.set_ore_budget(ore_budget);
 
     // first, get the general lower bound for what to guess
-    // 1 trillion / amount to make 1 fuel
-    // using this, x - (x % 10000)
-    nanofactory.produce_one_fuel()?;
-    println!("Ore usage for one fuel is {}", nanofactory.ore_usage);
-    let lower_bound = 1_000_000_000_000 / nanofactory.ore_usage;
-    println!("Initial lower bound is {}", lower_bound);
+    // ore budget / amount to make 1 fuel
+    if nanofactory.produce_one_fuel().is_err() {
+        eprintln!("A single fuel needs more than {} ore, so no fuel can be made", ore_budget);
+        return Ok(0);
+    }
+    if debug::verbose() {
+        eprintln!("Ore usage for one fuel is {}", nanofactory.ore_usage);
+    }
+    let lower_bound = ore_budget / nanofactory.ore_usage;
+    if debug::verbose() {
+        eprintln!("Initial lower bound is {}", lower_bound);
+    }
     nanofactory.wipe_everything();
-    let lower_bound = lower_bound - (lower_bound % 10_000);
-
-    println!("Initial lower bound is now {}", lower_bound);
 
     let max_fuel = nanofactory.max_fuel_output(lower_bound)?;
 
@@ -331,6 +450,27 @@ fn _q2(recipes: Vec<String>) -> Result<usize> {
 mod tests {
     use super::*;
 
+    // the third sample in the puzzle, which several tests share
+    const SAMPLE_3: &str = "
+        171 ORE => 8 CNZTR
+        7 ZLQW, 3 BMBT, 9 XCVML, 26 XMNCP, 1 WPTQ, 2 MZWV, 1 RJRHP => 4 PLWSL
+        114 ORE => 4 BHXH
+        14 VRPVC => 6 BMBT
+        6 BHXH, 18 KTJDG, 12 WPTQ, 7 PLWSL, 31 FHTLT, 37 ZDVW => 1 FUEL
+        6 WPTQ, 2 BMBT, 8 ZLQW, 18 KTJDG, 1 XMNCP, 6 MZWV, 1 RJRHP => 6 FHTLT
+        15 XDBXC, 2 LTCX, 1 VRPVC => 6 ZLQW
+        13 WPTQ, 10 LTCX, 3 RJRHP, 14 XMNCP, 2 MZWV, 1 ZLQW => 1 ZDVW
+        5 BMBT => 4 WPTQ
+        189 ORE => 9 KTJDG
+        1 MZWV, 17 XDBXC, 3 XCVML => 2 XMNCP
+        12 VRPVC, 27 CNZTR => 2 XDBXC
+        15 KTJDG, 12 BHXH => 5 XCVML
+        3 BHXH, 2 VRPVC => 7 MZWV
+        121 ORE => 7 VRPVC
+        7 XCVML => 6 RJRHP
+        5 BHXH, 4 VRPVC => 5 LTCX
+    ";
+
     #[test]
     fn day14_q1_test1() {
         let recipe_list : Vec<String> = "
@@ -348,6 +488,32 @@ mod tests {
         )
     }
 
+    #[test]
+    fn day14_reaction_count_test() {
+        let recipe_list : Vec<String> = "
+            10 ORE => 10 A
+            1 ORE => 1 B
+            7 A, 1 B => 1 C
+            7 A, 1 C => 1 D
+            7 A, 1 D => 1 E
+            7 A, 1 E => 1 FUEL
+        ".trim().lines().map(|l| l.trim().to_string()).collect();
+
+        let mut nanofactory = Nanofactory::new(recipe_list).unwrap();
+        nanofactory.produce_one_fuel().unwrap();
+
+        // FUEL, E, D, C and B once each, and A three times as the leftovers run out
+        assert_eq!(nanofactory.reaction_count(), 8);
+        assert_eq!(nanofactory.ore_usage, 31);
+
+        // checking what can be made doesn't count
+        assert!(nanofactory.can_produce("FUEL", 2));
+        assert_eq!(nanofactory.reaction_count(), 8);
+
+        nanofactory.wipe_everything();
+        assert_eq!(nanofactory.reaction_count(), 0);
+    }
+
     #[test]
     fn day14_q1_test2() {
         let recipe_list : Vec<String> = "
@@ -411,25 +577,7 @@ mod tests {
 
     #[test]
     fn day14_q1_test5() {
-        let recipe_list : Vec<String> = "
-            171 ORE => 8 CNZTR
-            7 ZLQW, 3 BMBT, 9 XCVML, 26 XMNCP, 1 WPTQ, 2 MZWV, 1 RJRHP => 4 PLWSL
-            114 ORE => 4 BHXH
-            14 VRPVC => 6 BMBT
-            6 BHXH, 18 KTJDG, 12 WPTQ, 7 PLWSL, 31 FHTLT, 37 ZDVW => 1 FUEL
-            6 WPTQ, 2 BMBT, 8 ZLQW, 18 KTJDG, 1 XMNCP, 6 MZWV, 1 RJRHP => 6 FHTLT
-            15 XDBXC, 2 LTCX, 1 VRPVC => 6 ZLQW
-            13 WPTQ, 10 LTCX, 3 RJRHP, 14 XMNCP, 2 MZWV, 1 ZLQW => 1 ZDVW
-            5 BMBT => 4 WPTQ
-            189 ORE => 9 KTJDG
-            1 MZWV, 17 XDBXC, 3 XCVML => 2 XMNCP
-            12 VRPVC, 27 CNZTR => 2 XDBXC
-            15 KTJDG, 12 BHXH => 5 XCVML
-            3 BHXH, 2 VRPVC => 7 MZWV
-            121 ORE => 7 VRPVC
-            7 XCVML => 6 RJRHP
-            5 BHXH, 4 VRPVC => 5 LTCX
-        ".trim().lines().map(|l| l.trim().to_string()).collect();
+        let recipe_list : Vec<String> = SAMPLE_3.trim().lines().map(|l| l.trim().to_string()).collect();
 
         assert_eq!(
             _q1(recipe_list).unwrap(),
@@ -482,29 +630,159 @@ mod tests {
 
     #[test]
     fn day14_q2_test3() {
-        let recipe_list : Vec<String> = "
-            171 ORE => 8 CNZTR
-            7 ZLQW, 3 BMBT, 9 XCVML, 26 XMNCP, 1 WPTQ, 2 MZWV, 1 RJRHP => 4 PLWSL
-            114 ORE => 4 BHXH
-            14 VRPVC => 6 BMBT
-            6 BHXH, 18 KTJDG, 12 WPTQ, 7 PLWSL, 31 FHTLT, 37 ZDVW => 1 FUEL
-            6 WPTQ, 2 BMBT, 8 ZLQW, 18 KTJDG, 1 XMNCP, 6 MZWV, 1 RJRHP => 6 FHTLT
-            15 XDBXC, 2 LTCX, 1 VRPVC => 6 ZLQW
-            13 WPTQ, 10 LTCX, 3 RJRHP, 14 XMNCP, 2 MZWV, 1 ZLQW => 1 ZDVW
-            5 BMBT => 4 WPTQ
-            189 ORE => 9 KTJDG
-            1 MZWV, 17 XDBXC, 3 XCVML => 2 XMNCP
-            12 VRPVC, 27 CNZTR => 2 XDBXC
-            15 KTJDG, 12 BHXH => 5 XCVML
-            3 BHXH, 2 VRPVC => 7 MZWV
-            121 ORE => 7 VRPVC
-            7 XCVML => 6 RJRHP
-            5 BHXH, 4 VRPVC => 5 LTCX
-        ".trim().lines().map(|l| l.trim().to_string()).collect();
+        let recipe_list : Vec<String> = SAMPLE_3.trim().lines().map(|l| l.trim().to_string()).collect();
 
         assert_eq!(
             _q2(recipe_list).unwrap(),
             460664
         )
     }
+
+    #[test]
+    fn day14_smaller_ore_budget_test() {
+        let recipe_list : Vec<String> = SAMPLE_3.trim().lines().map(|l| l.trim().to_string()).collect();
+
+        // one fuel takes 2210736 ORE
+        assert_eq!(
+            max_fuel_from_ore(recipe_list.clone(), 1_000_000).unwrap(),
+            0
+        );
+        assert_eq!(
+            max_fuel_from_ore(recipe_list.clone(), 2_210_736).unwrap(),
+            1
+        );
+        assert_eq!(
+            max_fuel_from_ore(recipe_list.clone(), 10_000_000).unwrap(),
+            4
+        );
+        assert_eq!(
+            max_fuel_from_ore(recipe_list, DEFAULT_ORE_BUDGET).unwrap(),
+            460664
+        );
+    }
+
+    #[test]
+    fn day14_produce_until_exhausted_test() {
+        let recipe_list : Vec<String> = SAMPLE_3.trim().lines().map(|l| l.trim().to_string()).collect();
+
+        let mut nanofactory = Nanofactory::new(recipe_list).unwrap();
+        for &ore_budget in [1_000_000, 2_210_736, 10_000_000, 25_000_000].iter() {
+            let one_at_a_time = nanofactory.produce_until_exhausted(ore_budget);
+
+            nanofactory.wipe_everything();
+            nanofactory.set_ore_budget(ore_budget);
+            assert_eq!(
+                one_at_a_time,
+                nanofactory.max_fuel_output(0).unwrap(),
+                "with {} ore", ore_budget
+            );
+        }
+        assert_eq!(nanofactory.produce_until_exhausted(10_000_000), 4);
+    }
+
+    #[test]
+    fn day14_can_produce_test() {
+        let recipe_list : Vec<String> = SAMPLE_3.trim().lines().map(|l| l.trim().to_string()).collect();
+
+        let mut nanofactory = Nanofactory::new(recipe_list).unwrap();
+        nanofactory.produce_one_fuel().unwrap();
+        let before = nanofactory.clone();
+
+        assert!(nanofactory.can_produce("FUEL", 1));
+        assert!(nanofactory.can_produce("FUEL", 460664));
+        assert!(!nanofactory.can_produce("FUEL", 460665));
+        assert!(!nanofactory.can_produce("FUEL", 1_000_000_000_000));
+        assert!(!nanofactory.can_produce("PLUTONIUM", 1));
+
+        assert_eq!(nanofactory, before);
+    }
+
+    #[test]
+    fn day14_ore_cost_test() {
+        let recipe_list : Vec<String> = SAMPLE_3.trim().lines().map(|l| l.trim().to_string()).collect();
+
+        let nanofactory = Nanofactory::new(recipe_list).unwrap();
+
+        assert_eq!(nanofactory.ore_cost_of("CNZTR").unwrap(), 171.0 / 8.0);
+        assert_eq!(nanofactory.ore_cost_of("BHXH").unwrap(), 114.0 / 4.0);
+        assert!(nanofactory.ore_cost_of("CNZTR").unwrap() < nanofactory.ore_cost_of("BHXH").unwrap());
+
+        // 3 BHXH, 2 VRPVC => 7 MZWV still needs a whole batch of each input: 114 + 121 ORE
+        assert_eq!(nanofactory.ore_cost_of("MZWV").unwrap(), (114.0 + 121.0) / 7.0);
+
+        assert_eq!(nanofactory.ore_cost_of("FUEL").unwrap(), 2210736.0);
+        assert_eq!(nanofactory.ore_cost_of("ORE").unwrap(), 1.0);
+        assert!(nanofactory.ore_cost_of("PLUTONIUM").is_err());
+    }
+
+    #[test]
+    fn day14_q2_unaffordable_test() {
+        let recipe_list : Vec<String> = "
+            1000000000001 ORE => 1 FUEL
+        ".trim().lines().map(|l| l.trim().to_string()).collect();
+
+        assert_eq!(
+            _q2(recipe_list.clone()).unwrap(),
+            0
+        );
+
+        let mut nanofactory = Nanofactory::new(recipe_list).unwrap();
+        assert_eq!(
+            nanofactory.max_fuel_output(0).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn day14_topological_order_test() {
+        let recipe_list : Vec<String> = "
+            10 ORE => 10 A
+            1 ORE => 1 B
+            7 A, 1 B => 1 C
+            7 A, 1 C => 1 D
+            7 A, 1 D => 1 E
+            7 A, 1 E => 1 FUEL
+        ".trim().lines().map(|l| l.trim().to_string()).collect();
+
+        let order = Nanofactory::new(recipe_list).unwrap().topological_order().unwrap();
+        let position = |chemical: &str| order.iter().position(|c| c == chemical).unwrap();
+
+        assert_eq!(order.len(), 7);
+        assert_eq!(position("ORE"), 0);
+        assert_eq!(position("FUEL"), 6);
+        for &raw in &["A", "B"] {
+            assert!(position(raw) < position("C"));
+        }
+        assert!(position("C") < position("D"));
+        assert!(position("D") < position("E"));
+        assert!(position("E") < position("FUEL"));
+    }
+
+    #[test]
+    fn day14_topological_order_cycle_test() {
+        let recipe_list : Vec<String> = "
+            1 ORE, 1 B => 1 A
+            1 A => 1 B
+            1 B => 1 FUEL
+        ".trim().lines().map(|l| l.trim().to_string()).collect();
+
+        assert!(Nanofactory::new(recipe_list).unwrap().topological_order().is_err());
+    }
+
+    #[test]
+    fn day14_recipe_display_test() {
+        let recipe_list : Vec<String> = "
+            10 ORE => 10 A
+            7 A, 1 B => 1 C
+            1 ORE => 1 B
+        ".trim().lines().map(|l| l.trim().to_string()).collect();
+
+        let nanofactory = Nanofactory::new(recipe_list.clone()).unwrap();
+
+        assert_eq!(nanofactory.recipes["C"].to_string(), "7 A, 1 B => 1 C");
+        for recipe in recipe_list {
+            let output: Material = recipe.split(" => ").last().unwrap().parse().unwrap();
+            assert_eq!(nanofactory.recipes[&output.chemical].to_string(), recipe);
+        }
+    }
 }
@@ -2,31 +2,25 @@ use std::cmp;
 use std::error::Error;
 use std::fs::File;
 use std::fmt;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
 
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
+use debug;
+use intcode::Program;
+use tile_map::TileMap;
+
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
+// From a BFS: how far away each open square is, and the square each one was first reached from
+type SearchTree = (BTreeMap<Coordinate, usize>, BTreeMap<Coordinate, Coordinate>);
+
 macro_rules! err {
     ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
-
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
 struct Coordinate {
     x: i32,
@@ -46,6 +40,19 @@ impl Coordinate {
             Coordinate::new(self.x, self.y - 1)
         ]
     }
+
+    // neighbours, plus the four diagonal squares
+    fn neighbours_with_diagonals(&self) -> Vec<Coordinate> {
+        let mut result = self.neighbours();
+        result.extend(vec![
+            Coordinate::new(self.x - 1, self.y + 1),
+            Coordinate::new(self.x + 1, self.y + 1),
+            Coordinate::new(self.x - 1, self.y - 1),
+            Coordinate::new(self.x + 1, self.y - 1)
+        ]);
+
+        result
+    }
 }
 
 impl Ord for Coordinate {
@@ -104,291 +111,85 @@ impl fmt::Display for Coordinate {
 }
 
 #[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
-enum Parameter {
-    Position,
-    Immediate,
-    Relative
-}
-
-#[derive(Clone, Eq, Default, Debug, PartialEq, Hash)]
-struct Instruction {
-    opcode: usize,
-    parameters: Vec<Parameter>
-}
-
-impl Instruction {
-    fn new(number: usize) -> Result<Instruction> {
-        let opcode = number % 100;
-        let mut digit_list: Vec<_> = (number / 100).to_string().chars().map(|d| d.to_digit(10).unwrap()).collect();
-        digit_list.reverse();
-
-        let params_length = match opcode {
-            1 => 3,
-            2 => 3,
-            3 => 1,
-            4 => 1,
-            5 => 2,
-            6 => 2,
-            7 => 3,
-            8 => 3,
-            9 => 1,
-            99 => 0,
-            x => return err!("{}", format!("Cannot read opcode: {}", x))
-        };
-
-        digit_list.resize(params_length, 0);
-        let parameters: Result<Vec<Parameter>> = digit_list.into_iter().map(|d| match d {
-            0 => Ok(Parameter::Position),
-            1 => Ok(Parameter::Immediate),
-            2 => Ok(Parameter::Relative),
-            x => err!("{}", format!("Cannot read parameter digit: {}", x))
-        }).collect();
-        let parameters = parameters?;
-
-        Ok(
-            Instruction {
-                opcode,
-                parameters,
-            }
-        )
-
-    }
-}
-
-struct Program {
-    memory: Vec<i64>,
-    input: i64,
-    current_input: usize,
-    pointer_idx: usize,
-    relative_base: i64,
+enum SquareType {
+    Wall,
+    Open,
+    System
 }
 
-impl Program {
-    fn new(memory: Vec<i64>)  -> Program {
-        Program {
-            memory,
-            input: 0,
-            current_input: 1,
-            pointer_idx: 0,
-            relative_base: 0,
+impl fmt::Display for SquareType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SquareType::*;
+        match &self {
+            Wall => write!(f, "#"),
+            Open => write!(f, "."),
+            System => write!(f, "x"),
         }
     }
+}
 
-    fn get_input(&mut self) -> Result<i64> {
-        Ok(self.input as i64)
-    }
-
-    fn set_input(&mut self, input: i64) {
-        self.input = input;
+// A saved map starts with the coordinate of its top left character and a legend, followed by
+// the rows from the top down with a space wherever hasn't been explored, e.g.
+//   origin -1,2
+//   legend #=Wall .=Open x=System
+fn to_text(map: &BTreeMap<Coordinate, SquareType>) -> String {
+    let mut tile_map: TileMap<SquareType> = TileMap::new(' ', true);
+    for (coord, &square_type) in map.iter() {
+        tile_map.insert(coord.x as i64, coord.y as i64, square_type);
     }
 
-    fn get_parameter(&mut self, parameter_form: Parameter, val: i64) -> i64 {
-        use self::Parameter::*;
+    let (origin_x, origin_y) = match tile_map.bounding_box() {
+        Some(((min_x, _), (_, max_y))) => (min_x, max_y),
+        None => (0, 0)
+    };
 
-        match parameter_form {
-            Position => {
-                let idx = val as usize;
-                if self.memory.len() < idx+1 {
-                    self.memory.resize(idx+1, 0);
-                }
-
-                self.memory[idx]
-            },
-            Immediate => val,
-            Relative => {
-                let idx = (self.relative_base + val) as usize;
-                if self.memory.len() < idx+1 {
-                    self.memory.resize(idx+1, 0);
-                }
+    format!("origin {},{}\nlegend #=Wall .=Open x=System\n{}", origin_x, origin_y, tile_map)
+}
 
-                self.memory[idx]
-            }
-        }
+fn from_text(text: &str) -> Result<BTreeMap<Coordinate, SquareType>> {
+    let mut lines = text.lines();
+
+    let origin = lines.next().ok_or("Missing origin line")?;
+    let origin: Vec<i32> = origin.trim_start_matches("origin ")
+        .split(',')
+        .map(|n| n.trim().parse())
+        .collect::<result::Result<_, _>>()
+        .map_err(|_| format!("Cannot read origin: {}", origin))?;
+    if origin.len() != 2 {
+        return err!("Origin should be x,y");
     }
 
-    fn set_parameter(&mut self, idx: usize, val: i64) -> Result<()> {
-        if self.memory.len() < idx+1 {
-            self.memory.resize(idx+1, 0);
-        }
-
-        self.memory[idx] = val;
-
-        Ok(())
-    }
+    let legend_line = lines.next().ok_or("Missing legend line")?;
+    let mut legend: BTreeMap<char, SquareType> = BTreeMap::new();
+    for entry in legend_line.trim_start_matches("legend ").split_whitespace() {
+        let mut chars = entry.chars();
+        let (c, name) = match (chars.next(), chars.next()) {
+            (Some(c), Some('=')) => (c, chars.as_str()),
+            _ => return err!("Cannot read legend entry: {}", entry)
+        };
 
-    fn get_output_idx(&mut self, idx: usize, parameter_type: Parameter) -> usize {
-        use self::Parameter::*;
-        if self.memory.len() < idx+1 {
-            self.memory.resize(idx+1, 0);
-        }
-        match parameter_type {
-            Position => {
-                self.memory[idx] as usize
-            },
-            Relative => {
-                (self.memory[idx] + self.relative_base) as usize
-            },
-            _ => panic!("Should never be here")
-        }
+        let square_type = match name {
+            "Wall" => SquareType::Wall,
+            "Open" => SquareType::Open,
+            "System" => SquareType::System,
+            x => return err!("Unknown square type in legend: {}", x)
+        };
+        legend.insert(c, square_type);
     }
 
-    fn run_program(&mut self) -> Result<Option<i64>> {
-        loop {
-            let current_instruction = Instruction::new(self.memory[self.pointer_idx] as usize)?;
-
-            match current_instruction.opcode {
-                1 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, input_1 + input_2)?;
-
-                    self.pointer_idx += 4;
-                },
-                2 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, input_1 * input_2)?;
-
-                    self.pointer_idx += 4;
-                },
-                3 => {
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 1,
-                        current_instruction.parameters[0]
-                    );
-                    let input = self.get_input()?;
-                    self.set_parameter(output_idx, input)?;
-
-                    self.pointer_idx += 2;
-                },
-                4 => {
-                    let output_val = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1]
-                    );
-
-                    // let output_idx = self.memory[self.pointer_idx+1];
-                    self.pointer_idx += 2;
-
-                    return Ok(Some(output_val));
-                },
-                5 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    if input_1 != 0 {
-                        self.pointer_idx = input_2 as usize;
-                    } else {
-                        self.pointer_idx += 3;
-                    }
-                },
-                6 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    if input_1 == 0 {
-                        self.pointer_idx = input_2 as usize;
-                    } else {
-                        self.pointer_idx += 3;
-                    }
-                },
-                7 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, if input_1 < input_2 {1} else {0})?;
-
-                    self.pointer_idx += 4;
-                },
-                8 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    let input_2 = self.get_parameter(
-                        current_instruction.parameters[1],
-                        self.memory[self.pointer_idx+2],
-                    );
-                    let output_idx = self.get_output_idx(
-                        self.pointer_idx + 3,
-                        current_instruction.parameters[2]
-                    );
-                    self.set_parameter(output_idx, if input_1 == input_2 {1} else {0})?;
-
-                    self.pointer_idx += 4;
-                },
-                9 => {
-                    let input_1 = self.get_parameter(
-                        current_instruction.parameters[0],
-                        self.memory[self.pointer_idx+1],
-                    );
-                    self.relative_base += input_1;
-
-                    self.pointer_idx += 2;
-                },
-                99 => break,
-                x => return err!("{}", format!("Incorrect opcode: {}", x))
+    let mut map = BTreeMap::new();
+    for (row, line) in lines.enumerate() {
+        for (col, c) in line.chars().enumerate() {
+            if c == ' ' {
+                continue;
             }
-        }
-        Ok(None)
-    }
-}
 
-#[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
-enum SquareType {
-    Wall,
-    Open,
-    System
-}
-
-impl fmt::Display for SquareType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::SquareType::*;
-        match &self {
-            Wall => write!(f, "#"),
-            Open => write!(f, "."),
-            System => write!(f, "x"),
+            let square_type = *legend.get(&c).ok_or(format!("{} isn't in the legend", c))?;
+            map.insert(Coordinate::new(origin[0] + col as i32, origin[1] - row as i32), square_type);
         }
     }
+
+    Ok(map)
 }
 
 #[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
@@ -432,26 +233,123 @@ impl Direction {
     }
 }
 
+// What happened when the droid tried to move one square
+#[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
+enum CellResult {
+    Wall,
+    Moved,
+    FoundLeak
+}
+
+// The status codes used by the day 15 repair droid program
+fn decode_status(status: i64) -> Result<CellResult> {
+    match status {
+        0 => Ok(CellResult::Wall),
+        1 => Ok(CellResult::Moved),
+        2 => Ok(CellResult::FoundLeak),
+        x => err!("Unexpected output from program: {}", x)
+    }
+}
+
 struct Droid {
+    initial_memory: Vec<i64>,
     program: Program,
     floor_map: BTreeMap<Coordinate, SquareType>,
     leak_location: Coordinate,
     current_coord: Coordinate,
+    // distances over the open squares from the coordinate they were worked out from.
+    // Only new open squares can change them, so walls being found doesn't clear this.
+    distance_cache: Option<(Coordinate, SearchTree)>,
+    // how many paths have been asked for, and how many of them needed a fresh BFS
+    path_requests: usize,
+    bfs_runs: usize,
 }
 
 impl Droid {
     fn new(memory: Vec<i64>) -> Droid {
         Droid {
+            initial_memory: memory.clone(),
             program: Program::new(memory),
             floor_map: BTreeMap::new(),
             leak_location: Coordinate::new(0, 0),
-            current_coord: Coordinate::new(0, 0)
+            current_coord: Coordinate::new(0, 0),
+            distance_cache: None,
+            path_requests: 0,
+            bfs_runs: 0
+        }
+    }
+
+    // Forget everything explored so far and restart the program from its original memory
+    fn reset_exploration(&mut self) {
+        self.program = Program::new(self.initial_memory.clone());
+        self.floor_map.clear();
+        self.leak_location = Coordinate::new(0, 0);
+        self.current_coord = Coordinate::new(0, 0);
+        self.distance_cache = None;
+        self.path_requests = 0;
+        self.bfs_runs = 0;
+    }
+
+    // The map explored so far, in the to_text format
+    fn save_map(&self) -> String {
+        to_text(&self.floor_map)
+    }
+
+    // Replaces the explored map with a saved one, so the distances and oxygen spread can be
+    // worked out without running the program again. The program itself isn't restored, so
+    // don't explore any further after this.
+    fn load_map(&mut self, text: &str) -> Result<()> {
+        let floor_map = from_text(text)?;
+        let leak_location = floor_map.iter()
+            .find(|&(_, &square_type)| square_type == SquareType::System)
+            .map(|(&coord, _)| coord)
+            .ok_or("Saved map doesn't have the oxygen system on it")?;
+
+        self.floor_map = floor_map;
+        self.leak_location = leak_location;
+        self.distance_cache = None;
+
+        Ok(())
+    }
+
+    // Records a newly discovered square, keeping the distance cache up to date
+    fn discover(&mut self, coord: Coordinate, square_type: SquareType) -> bool {
+        let is_new = self.floor_map.insert(coord, square_type).is_none();
+        if is_new && square_type == SquareType::Open {
+            self.distance_cache = None;
+        }
+
+        is_new
+    }
+
+    // Same as shortest_path_from_to, but reuses the last BFS if it started from the same place
+    // and the open squares haven't changed since
+    fn cached_shortest_path_from_to(&mut self, from: Coordinate, to: Coordinate) -> Result<Vec<Coordinate>> {
+        self.path_requests += 1;
+
+        let is_cached = match self.distance_cache {
+            Some((cached_from, _)) => cached_from == from,
+            None => false
+        };
+        if !is_cached {
+            self.bfs_runs += 1;
+            self.distance_cache = Some((from, self.search_from(from, None)));
         }
+
+        let search_tree = &self.distance_cache.as_ref().unwrap().1;
+        self.path_from_search_tree(search_tree, from, to)
     }
 
     fn shortest_path_from_to(&self, from: Coordinate, to: Coordinate) -> Result<Vec<Coordinate>> {
+        let search_tree = self.search_from(from, Some(to));
+        self.path_from_search_tree(&search_tree, from, to)
+    }
+
+    // BFS over the open squares, stopping early once stop_at has been reached
+    fn search_from(&self, from: Coordinate, stop_at: Option<Coordinate>) -> SearchTree {
         let mut d = BTreeMap::new();
         d.insert(from, 0);
+        let mut came_from = BTreeMap::new();
 
         let mut queue: VecDeque<Coordinate> = VecDeque::new();
         queue.push_front(from);
@@ -461,7 +359,7 @@ impl Droid {
             todo_set.remove(&c);
             visited.insert(c);
 
-            if c == to {
+            if Some(c) == stop_at {
                 break;
             }
 
@@ -477,25 +375,36 @@ impl Droid {
                 let new_dist = 1 + *d.get(&c).unwrap_or(&0);
                 if !d.contains_key(&neighbour) || new_dist < d[&neighbour] {
                     d.insert(neighbour, new_dist);
+                    came_from.insert(neighbour, c);
                 }
             }
         }
 
+        (d, came_from)
+    }
+
+    // The path from from to to, both included. to doesn't have to be open (it can be a square
+    // that hasn't been explored yet), as long as it's next to an open square the BFS reached.
+    fn path_from_search_tree(&self, search_tree: &SearchTree, from: Coordinate, to: Coordinate) -> Result<Vec<Coordinate>> {
+        let (d, came_from) = search_tree;
+
         let mut path_to_take: Vec<Coordinate> = vec![to];
-        let mut current_position = to;
-        loop {
-            let next_position = current_position.neighbours().into_iter().filter(|&c| d.contains_key(&c)).min_by_key(|&c| d[&c]);
-            if next_position.is_none() {
-                println!("{}", self);
-                return err!("Cannot find any neighbours for {}", current_position);
+        let mut current_position = if to == from || came_from.contains_key(&to) {
+            to
+        } else {
+            let last_step = to.neighbours().into_iter().filter(|c| d.contains_key(c)).min_by_key(|c| d[c]);
+            match last_step {
+                Some(last_step) => {
+                    path_to_take.push(last_step);
+                    last_step
+                },
+                None => return err!("Cannot get to {} from {}", to, from)
             }
+        };
 
-            let next_position = next_position.unwrap();
-            path_to_take.push(next_position);
-            if next_position == from {
-                break;
-            }
-            current_position = next_position;
+        while current_position != from {
+            current_position = came_from[&current_position];
+            path_to_take.push(current_position);
         }
 
         path_to_take.reverse();
@@ -508,12 +417,41 @@ impl Droid {
         Ok(path.len() - 1)
     }
 
-    fn dist_to_leak(&self) -> Result<usize> {
-        self.steps_to_get_to(self.leak_location)
+    fn find_leak(&mut self, stop_on_leak: bool) -> Result<()> {
+        self.find_leak_with_updates(stop_on_leak, |_| {})
     }
 
-    fn find_leak(&mut self, stop_on_leak: bool) -> Result<()> {
-        self.floor_map.insert(self.current_coord, SquareType::Open);
+    fn has_found_leak(&self) -> bool {
+        self.floor_map.get(&self.leak_location) == Some(&SquareType::System)
+    }
+
+    // Explores until the oxygen system turns up and returns where it is
+    fn locate_leak(&mut self) -> Result<Coordinate> {
+        self.find_leak(true)?;
+
+        if !self.has_found_leak() {
+            return err!("Explored the whole area without finding the oxygen system");
+        }
+
+        Ok(self.leak_location)
+    }
+
+    // Same as find_leak, but calls on_update with the floor map every time a new square is
+    // discovered, so that a caller can render the exploration however (and as often) it likes
+    fn find_leak_with_updates<F>(&mut self, stop_on_leak: bool, on_update: F) -> Result<()>
+        where F: FnMut(&BTreeMap<Coordinate, SquareType>)
+    {
+        self.explore(stop_on_leak, decode_status, on_update)
+    }
+
+    // The exploration itself, for any program whose outputs can be decoded into a CellResult
+    fn explore<D, F>(&mut self, stop_on_leak: bool, decode: D, mut on_update: F) -> Result<()>
+        where D: Fn(i64) -> Result<CellResult>,
+              F: FnMut(&BTreeMap<Coordinate, SquareType>)
+    {
+        if self.discover(self.current_coord, SquareType::Open) {
+            on_update(&self.floor_map);
+        }
 
         let mut current_target = Coordinate::new(0, 0);
 
@@ -534,43 +472,43 @@ impl Droid {
                 }
             }
 
-            // println!("Current target = {:?}", current_target);
-
-            let path_to_next_target = self.shortest_path_from_to(self.current_coord, current_target)?;
-
-            // println!("Path from {} to {} = {:?}", self.current_coord, current_target, path_to_next_target);
+            let path_to_next_target = self.cached_shortest_path_from_to(self.current_coord, current_target)?;
 
             let directions = convert_path_to_directions(path_to_next_target)?;
 
             for direction in directions {
-                // println!("Inputting {:?} ({}) into program", direction, direction.to_digit());
-                self.program.set_input(direction.to_digit());
-                if let Some(result) = self.program.run_program()? {
-                    match result {
-                        0 => {
+                self.program.add_input(direction.to_digit());
+                if let Some(result) = self.program.run_until_output()? {
+                    let next_coord = self.current_coord + direction.to_coordinate();
+                    match decode(result)? {
+                        CellResult::Wall => {
                             // hit a wall
-                            // println!("{} is a wall, droid doesn't move", self.current_coord + direction.to_coordinate());
-                            self.floor_map.insert(self.current_coord + direction.to_coordinate(), SquareType::Wall);
+                            if self.discover(next_coord, SquareType::Wall) {
+                                on_update(&self.floor_map);
+                            }
                             continue 'main;
                         },
-                        1 => {
+                        CellResult::Moved => {
                             // all is well
-                            // println!("{} is clear, droid moves", self.current_coord + direction.to_coordinate());
-                            self.floor_map.insert(self.current_coord + direction.to_coordinate(), SquareType::Open);
+                            if self.discover(next_coord, SquareType::Open) {
+                                on_update(&self.floor_map);
+                            }
                             self.current_coord += direction.to_coordinate();
                         },
-                        2 => {
+                        CellResult::FoundLeak => {
                             // moved and found leak!
-                            println!("Found leak at {}!", self.current_coord + direction.to_coordinate());
-                            // println!("{}", self);
-                            self.floor_map.insert(self.current_coord + direction.to_coordinate(), SquareType::System);
+                            if debug::verbose() {
+                                eprintln!("Found leak at {}!", next_coord);
+                            }
+                            if self.discover(next_coord, SquareType::System) {
+                                on_update(&self.floor_map);
+                            }
                             self.leak_location = self.current_coord + direction.to_coordinate();
                             self.current_coord += direction.to_coordinate();
                             if stop_on_leak {
                                 break 'main;
                             }
-                        },
-                        x => panic!("Unexpected output from program: {}", x)
+                        }
                     }
                 }
             }
@@ -579,66 +517,82 @@ impl Droid {
         Ok(())
     }
 
-    fn time_for_oxygen_spread(&mut self) -> Result<usize> {
+    // Cells the oxygen newly reaches at each minute, starting from the leak.
+    // The puzzle only spreads to the 4 adjacent squares; diagonal_spread lets it go diagonally too.
+    fn oxygen_fill_frontiers(&self, diagonal_spread: bool) -> Vec<Vec<Coordinate>> {
         let mut oxygen_squares: BTreeSet<Coordinate> = BTreeSet::new();
         oxygen_squares.insert(self.leak_location);
 
-        let mut t = 0;
+        let mut frontiers: Vec<Vec<Coordinate>> = Vec::new();
+        let mut frontier = vec![self.leak_location];
 
         loop {
-            let near_oxygen_squares: Vec<_> = oxygen_squares.iter()
-                .flat_map(|&coord| coord.neighbours())
-                .filter(|&coord| self.floor_map[&coord] == SquareType::Open && !oxygen_squares.contains(&coord)).collect();
+            let mut next_frontier = Vec::new();
+            let next_to_frontier = frontier.iter().flat_map(|coord| {
+                if diagonal_spread {
+                    coord.neighbours_with_diagonals()
+                } else {
+                    coord.neighbours()
+                }
+            });
+
+            for coord in next_to_frontier {
+                if self.floor_map.get(&coord) == Some(&SquareType::Open) && oxygen_squares.insert(coord) {
+                    next_frontier.push(coord);
+                }
+            }
 
-            if near_oxygen_squares.is_empty() {
+            if next_frontier.is_empty() {
                 break;
             }
 
-            oxygen_squares.extend(near_oxygen_squares.iter());
+            frontiers.push(next_frontier.clone());
+            frontier = next_frontier;
+        }
+
+        frontiers
+    }
+
+    fn time_for_oxygen_spread(&self, diagonal_spread: bool) -> Result<usize> {
+        Ok(self.oxygen_fill_frontiers(diagonal_spread).len())
+    }
 
-            t += 1;
+    // (steps from the start to the leak, minutes for the oxygen to fill the area) from a
+    // single exploration of the whole map
+    fn solve(&mut self) -> Result<(usize, usize)> {
+        self.find_leak(false)?;
+        if !self.has_found_leak() {
+            return err!("Explored the whole area without finding the oxygen system");
         }
 
-        Ok(t)
+        Ok((self.steps_to_get_to(self.leak_location)?, self.time_for_oxygen_spread(false)?))
     }
 }
 
 impl fmt::Display for Droid {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let first_x = self.floor_map.keys().map(|&c| c.x).min().unwrap();
-        let last_x = self.floor_map.keys().map(|&c| c.x).max().unwrap();
-        let first_y = self.floor_map.keys().map(|&c| c.y).min().unwrap();
-        let last_y = self.floor_map.keys().map(|&c| c.y).max().unwrap();
-
-        for y in (first_y..=last_y).rev() {
-            for x in first_x..=last_x {
-                let coord = Coordinate::new(x, y);
-                if coord == self.current_coord {
-                    write!(f, "D")?;
-                    continue;
-                }
-
-                if coord == Coordinate::new(0, 0) {
-                    write!(f, "O")?;
-                    continue;
-                }
-
-                match self.floor_map.get(&coord) {
-                    Some(square_type) => {
-                        write!(f, "{}", square_type)?;
-                    },
-                    None => {
-                        write!(f, " ")?;
-                    }
-                }
-            }
-            write!(f, "{}", '\n')?;
+        let mut tile_map: TileMap<String> = TileMap::new(' ', true);
+        for (coord, square_type) in self.floor_map.iter() {
+            tile_map.insert(coord.x as i64, coord.y as i64, square_type.to_string());
         }
 
-        Ok(())
+        tile_map.insert(0, 0, "O".to_string());
+        tile_map.insert(self.current_coord.x as i64, self.current_coord.y as i64, "D".to_string());
+
+        write!(f, "{}", tile_map)
     }
 }
 
+// Maps out the whole maze for a repair droid program with its own output encoding
+fn explore_maze<D>(memory: Vec<i64>, decode: D) -> Result<Droid>
+    where D: Fn(i64) -> Result<CellResult>
+{
+    let mut droid = Droid::new(memory);
+    droid.explore(false, decode, |_| {})?;
+
+    Ok(droid)
+}
+
 fn convert_path_to_directions(path: Vec<Coordinate>) -> Result<Vec<Direction>> {
     path.windows(2).map(|t| {
         use self::Direction::*;
@@ -669,8 +623,11 @@ pub fn q1(fname: String) -> usize {
 
 fn _q1(memory: Vec<i64>) -> Result<usize> {
     let mut droid = Droid::new(memory);
-    droid.find_leak(true)?;
-    droid.dist_to_leak()
+    let leak_location = droid.locate_leak()?;
+    if debug::verbose() {
+        eprintln!("{} paths needed {} BFS runs", droid.path_requests, droid.bfs_runs);
+    }
+    droid.steps_to_get_to(leak_location)
 }
 
 pub fn q2(fname: String) -> usize {
@@ -688,9 +645,296 @@ fn _q2(memory: Vec<i64>) -> Result<usize> {
     let mut droid = Droid::new(memory);
 
     droid.find_leak(false)?;
+    if debug::verbose() {
+        eprintln!("{} paths needed {} BFS runs", droid.path_requests, droid.bfs_runs);
+    }
 
     // Map has been completely filled in
-    println!("{}", droid);
+    if debug::verbose() {
+        eprintln!("{}", droid);
+    }
+
+    droid.time_for_oxygen_spread(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds an Intcode repair droid program for a hand-drawn maze, so that tests don't need
+    // the puzzle input. 'D' is the droid's starting square and 'O' is the oxygen system.
+    // The maze must be surrounded by walls.
+    fn maze_program(maze: &str) -> Vec<i64> {
+        maze_program_with_codes(maze, (0, 1, 2))
+    }
 
-    droid.time_for_oxygen_spread()
+    // codes are the outputs for (wall, open, oxygen system)
+    fn maze_program_with_codes(maze: &str, codes: (i64, i64, i64)) -> Vec<i64> {
+        let rows: Vec<Vec<char>> = maze.trim().lines().map(|l| l.trim().chars().collect()).collect();
+        let width = rows[0].len() as i64;
+
+        let (row, col, base) = (100, 101, 120);
+        let (next_row, next_col, direction, tile, tmp, idx) = (102, 103, 104, 105, 106, 107);
+
+        let mut memory: Vec<i64> = vec![
+            3, direction,
+            1008, direction, 1, tmp,
+            1002, tmp, -1, tmp,
+            1, row, tmp, next_row,
+            1008, direction, 2, tmp,
+            1, next_row, tmp, next_row,
+            1008, direction, 3, tmp,
+            1002, tmp, -1, tmp,
+            1, col, tmp, next_col,
+            1008, direction, 4, tmp,
+            1, next_col, tmp, next_col,
+            1002, next_row, width, idx,
+            1, idx, next_col, idx,
+            1001, idx, base, 55,
+            1001, 0, 0, tile,
+            4, tile,
+            1008, tile, codes.0, tmp,
+            1005, tmp, 0,
+            1001, next_row, 0, row,
+            1001, next_col, 0, col,
+            1105, 1, 0,
+        ];
+        memory.resize(base as usize, 0);
+
+        for (y, line) in rows.iter().enumerate() {
+            for (x, &c) in line.iter().enumerate() {
+                if c == 'D' {
+                    memory[row as usize] = y as i64;
+                    memory[col as usize] = x as i64;
+                }
+
+                memory.push(match c {
+                    '#' => codes.0,
+                    'O' => codes.2,
+                    _ => codes.1
+                });
+            }
+        }
+
+        memory
+    }
+
+    const TEST_MAZE: &str = "
+        #######
+        #D..#.#
+        #.#...#
+        #.##O.#
+        #######
+    ";
+
+    #[test]
+    fn day15_update_hook_test() {
+        let mut droid = Droid::new(maze_program(TEST_MAZE));
+        let mut update_count = 0;
+        droid.find_leak_with_updates(false, |_| update_count += 1).unwrap();
+
+        assert_eq!(
+            update_count,
+            droid.floor_map.len()
+        );
+        assert_eq!(
+            droid.leak_location,
+            Coordinate::new(3, -2)
+        );
+    }
+
+    #[test]
+    fn day15_locate_leak_test() {
+        let mut droid = Droid::new(maze_program(TEST_MAZE));
+        assert_eq!(droid.locate_leak().unwrap(), Coordinate::new(3, -2));
+        assert_eq!(droid.steps_to_get_to(Coordinate::new(3, -2)).unwrap(), 5);
+
+        let mut droid = Droid::new(maze_program("
+            #####
+            #D..#
+            #####
+        "));
+        assert!(droid.locate_leak().is_err());
+    }
+
+    #[test]
+    fn day15_solve_test() {
+        let mut droid = Droid::new(maze_program(TEST_MAZE));
+        assert_eq!(droid.solve().unwrap(), (5, 7));
+
+        // the same as q1 and q2 exploring separately
+        assert_eq!(_q1(maze_program(TEST_MAZE)).unwrap(), 5);
+        assert_eq!(_q2(maze_program(TEST_MAZE)).unwrap(), 7);
+    }
+
+    #[test]
+    fn day15_oxygen_frontiers_test() {
+        let mut droid = Droid::new(maze_program(TEST_MAZE));
+        droid.find_leak(false).unwrap();
+
+        let frontiers = droid.oxygen_fill_frontiers(false);
+        assert_eq!(frontiers.len(), 7);
+        assert_eq!(droid.time_for_oxygen_spread(false).unwrap(), frontiers.len());
+
+        let filled: BTreeSet<Coordinate> = frontiers.into_iter().flatten().collect();
+        let open: BTreeSet<Coordinate> = droid.floor_map.iter()
+            .filter(|&(_, &square_type)| square_type == SquareType::Open)
+            .map(|(&coord, _)| coord)
+            .collect();
+        assert_eq!(filled, open);
+    }
+
+    #[test]
+    fn day15_reset_test() {
+        let mut droid = Droid::new(maze_program(TEST_MAZE));
+        droid.find_leak(false).unwrap();
+        let explored_map = droid.floor_map.clone();
+
+        droid.reset_exploration();
+        assert!(droid.floor_map.is_empty());
+        assert_eq!(droid.current_coord, Coordinate::new(0, 0));
+
+        droid.find_leak(false).unwrap();
+        assert_eq!(droid.floor_map, explored_map);
+        assert_eq!(droid.leak_location, Coordinate::new(3, -2));
+    }
+
+    #[test]
+    fn day15_explore_maze_test() {
+        let remapped = maze_program_with_codes(TEST_MAZE, (7, 8, 9));
+        let droid = explore_maze(remapped, |status| match status {
+            7 => Ok(CellResult::Wall),
+            8 => Ok(CellResult::Moved),
+            9 => Ok(CellResult::FoundLeak),
+            x => err!("Unexpected output from program: {}", x)
+        }).unwrap();
+
+        let mut expected = Droid::new(maze_program(TEST_MAZE));
+        expected.find_leak(false).unwrap();
+
+        assert_eq!(droid.floor_map, expected.floor_map);
+        assert_eq!(droid.leak_location, Coordinate::new(3, -2));
+
+        // the day 15 decoding doesn't understand the remapped outputs
+        let remapped = maze_program_with_codes(TEST_MAZE, (7, 8, 9));
+        assert!(explore_maze(remapped, decode_status).is_err());
+    }
+
+    #[test]
+    fn day15_junction_path_test() {
+        //   #
+        //   .#
+        // ....
+        let mut droid = Droid::new(vec![99]);
+        for &(x, y) in [(0, 0), (1, 0), (2, 0), (3, 0), (2, 1), (2, 2)].iter() {
+            droid.floor_map.insert(Coordinate::new(x, y), SquareType::Open);
+        }
+        droid.floor_map.insert(Coordinate::new(3, 1), SquareType::Wall);
+
+        let coords = |points: &[(i32, i32)]| -> Vec<Coordinate> {
+            points.iter().map(|&(x, y)| Coordinate::new(x, y)).collect()
+        };
+        let from = Coordinate::new(0, 0);
+
+        assert_eq!(
+            droid.shortest_path_from_to(from, Coordinate::new(2, 2)).unwrap(),
+            coords(&[(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)])
+        );
+        assert_eq!(
+            droid.shortest_path_from_to(from, Coordinate::new(3, 0)).unwrap(),
+            coords(&[(0, 0), (1, 0), (2, 0), (3, 0)])
+        );
+        assert_eq!(droid.shortest_path_from_to(from, from).unwrap(), coords(&[(0, 0)]));
+
+        // an unexplored square just past the end of the corridor
+        assert_eq!(
+            droid.shortest_path_from_to(from, Coordinate::new(4, 0)).unwrap(),
+            coords(&[(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)])
+        );
+        assert!(droid.shortest_path_from_to(from, Coordinate::new(10, 10)).is_err());
+    }
+
+    #[test]
+    fn day15_distance_cache_test() {
+        let mut droid = Droid::new(maze_program(TEST_MAZE));
+        droid.find_leak(false).unwrap();
+
+        // hitting a wall doesn't need a new BFS
+        assert!(droid.bfs_runs < droid.path_requests);
+
+        let from = droid.current_coord;
+        let targets: Vec<Coordinate> = droid.floor_map.iter()
+            .filter(|&(&coord, &square_type)| square_type == SquareType::Open && coord != from)
+            .map(|(&coord, _)| coord)
+            .collect();
+        for target in targets {
+            assert_eq!(
+                droid.cached_shortest_path_from_to(from, target).unwrap(),
+                droid.shortest_path_from_to(from, target).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn day15_map_text_test() {
+        let mut droid = Droid::new(maze_program(TEST_MAZE));
+        droid.find_leak(false).unwrap();
+
+        let text = to_text(&droid.floor_map);
+        assert_eq!(
+            text,
+            "origin -1,1\nlegend #=Wall .=Open x=System\n ### # \n#...#.#\n#.#...#\n#.##x.#\n #   # \n"
+        );
+        assert_eq!(from_text(&text).unwrap(), droid.floor_map);
+
+        // gaps in the exploration stay unexplored
+        let mut map = BTreeMap::new();
+        map.insert(Coordinate::new(0, 0), SquareType::Open);
+        map.insert(Coordinate::new(2, -1), SquareType::System);
+        assert_eq!(to_text(&map), "origin 0,0\nlegend #=Wall .=Open x=System\n.  \n  x\n");
+        assert_eq!(from_text(&to_text(&map)).unwrap(), map);
+
+        assert!(from_text("origin 0,0\nlegend #=Wall\n#.").is_err());
+    }
+
+    #[test]
+    fn day15_save_map_test() {
+        let mut droid = Droid::new(maze_program(TEST_MAZE));
+        droid.find_leak(false).unwrap();
+        let saved = droid.save_map();
+
+        // a program that does nothing, so everything has to come from the saved map
+        let mut reloaded = Droid::new(vec![99]);
+        reloaded.load_map(&saved).unwrap();
+
+        assert_eq!(reloaded.floor_map, droid.floor_map);
+        assert_eq!(reloaded.leak_location, Coordinate::new(3, -2));
+        assert_eq!(
+            reloaded.time_for_oxygen_spread(false).unwrap(),
+            droid.time_for_oxygen_spread(false).unwrap()
+        );
+        assert_eq!(reloaded.steps_to_get_to(reloaded.leak_location).unwrap(), 5);
+
+        let mut no_system = Droid::new(vec![99]);
+        assert!(no_system.load_map("origin 0,0\nlegend #=Wall .=Open x=System\n#.#\n").is_err());
+    }
+
+    #[test]
+    fn day15_diagonal_spread_test() {
+        // O..
+        // ##.
+        // ##.
+        let mut droid = Droid::new(vec![99]);
+        droid.floor_map.insert(Coordinate::new(0, 0), SquareType::System);
+        for &(x, y) in &[(1, 0), (2, 0), (2, -1), (2, -2)] {
+            droid.floor_map.insert(Coordinate::new(x, y), SquareType::Open);
+        }
+        for &(x, y) in &[(0, -1), (1, -1), (0, -2), (1, -2)] {
+            droid.floor_map.insert(Coordinate::new(x, y), SquareType::Wall);
+        }
+
+        // the oxygen can cut the corner from (1, 0) to (2, -1)
+        assert_eq!(droid.time_for_oxygen_spread(false).unwrap(), 4);
+        assert_eq!(droid.time_for_oxygen_spread(true).unwrap(), 3);
+    }
 }
@@ -1,23 +1,16 @@
 use std::error::Error;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 use std::result;
 
 use itertools::Itertools;
 
-type Result<T> = result::Result<T, Box<dyn Error>>;
-
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
+use debug;
 
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
+type Result<T> = result::Result<T, Box<dyn Error>>;
 
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
 struct Signal {
@@ -38,6 +31,20 @@ impl Signal {
         Ok(())
     }
 
+    // Runs phases until one leaves the signal unchanged, giving (phases run, whether it stabilised)
+    fn iterate_until_stable(&mut self, max_phases: usize) -> Result<(usize, bool)> {
+        for phase in 1..=max_phases {
+            let previous_numbers = self.numbers.clone();
+            self.fft_iterate()?;
+
+            if self.numbers == previous_numbers {
+                return Ok((phase, true));
+            }
+        }
+
+        Ok((max_phases, false))
+    }
+
     fn shorter_fft_iterate(&mut self, target: usize) -> Result<()> {
         let mut current_idx = self.numbers.len() - 1;
 
@@ -73,11 +80,36 @@ fn fft_pattern(step: usize, size: usize) -> Vec<i32> {
     result
 }
 
-fn fft_step_for(numbers: &Vec<i32>, n: usize) -> i32 {
+// The nth element of the next phase before it's cut down to its ones digit, i.e. the signed
+// sum of the signal times the pattern
+fn fft_step_raw(numbers: &[i32], n: usize) -> i32 {
     let pattern = fft_pattern(n, numbers.len());
 
-    let sum_number: i32 = numbers.iter().zip(pattern.iter()).map(|(x, y)| x * y).sum();
-    sum_number.abs() % 10
+    numbers.iter().zip(pattern.iter()).map(|(x, y)| x * y).sum()
+}
+
+fn fft_step_for(numbers: &[i32], n: usize) -> i32 {
+    fft_step_raw(numbers, n).abs() % 10
+}
+
+// Accepts either a contiguous digit string or digits separated by commas/whitespace
+fn parse_signal(s: &str) -> Result<Vec<i32>> {
+    let s = s.trim();
+    let is_separator = |c: char| c == ',' || c.is_whitespace();
+
+    let tokens: Vec<String> = if s.contains(is_separator) {
+        s.split(is_separator).filter(|token| !token.is_empty()).map(|token| token.to_string()).collect()
+    } else {
+        s.chars().map(|c| c.to_string()).collect()
+    };
+
+    tokens.into_iter().map(|token| {
+        let mut chars = token.chars();
+        match (chars.next().and_then(|c| c.to_digit(10)), chars.next()) {
+            (Some(digit), None) => Ok(digit as i32),
+            _ => err!("Signal must be single digits, found: {}", token)
+        }
+    }).collect()
 }
 
 pub fn q1(fname: String) -> String {
@@ -86,7 +118,7 @@ pub fn q1(fname: String) -> String {
 
     f.read_to_string(&mut f_contents).expect("Couldn't find file");
 
-    let number_list: Vec<i32> = f_contents.trim().chars().map(|s| s.to_digit(10).unwrap() as i32).collect();
+    let number_list: Vec<i32> = parse_signal(&f_contents).unwrap();
 
     _q1(number_list).unwrap()
 }
@@ -109,28 +141,45 @@ pub fn q2(fname: String) -> String {
 
     f.read_to_string(&mut f_contents).expect("Couldn't find file");
 
-    let number_list: Vec<i32> = f_contents.trim().chars().map(|s| s.to_digit(10).unwrap() as i32).collect();
+    let number_list: Vec<i32> = parse_signal(&f_contents).unwrap();
 
     _q2(number_list).unwrap()
 }
 
 
 fn _q2(number_list: Vec<i32>) -> Result<String> {
+    if number_list.len() < 7 {
+        return err!("Signal has {} digits, it needs at least 7 for the message offset", number_list.len());
+    }
     let offset: usize = number_list[..7].iter().join("").parse()?;
 
+    // shorter_fft_iterate only works out the second half of the signal, where every
+    // pattern is zeros followed by ones
+    let total_length = number_list.len() * 10_000;
+    if offset + 8 > total_length {
+        return err!("Message offset {} is past the end of a signal of length {}", offset, total_length);
+    }
+    if offset < total_length / 2 {
+        return err!("Message offset {} is in the first half of a signal of length {}", offset, total_length);
+    }
+
     let mut extended_list: Vec<i32> = vec![];
     for _ in 0..10_000 {
         extended_list.extend(&number_list);
     }
 
-    println!("Length of number list = {}", extended_list.len());
-    println!("Fraction through list = {}", (offset as f32 / extended_list.len() as f32));
+    if debug::verbose() {
+        eprintln!("Length of number list = {}", extended_list.len());
+        eprintln!("Fraction through list = {}", (offset as f32 / extended_list.len() as f32));
+    }
 
     let mut signal = Signal::new(extended_list);
 
     for iteration in 0..100 {
         signal.shorter_fft_iterate(offset)?;
-        println!("Completed {} iterations", iteration);
+        if debug::verbose() {
+            eprintln!("Completed {} iterations", iteration);
+        }
     }
 
     Ok(
@@ -200,4 +249,58 @@ mod tests {
             "53553731".to_string()
         )
     }
+
+    #[test]
+    fn day16_q2_offset_test() {
+        match _q2(parse_signal("12345").unwrap()) {
+            Err(e) => assert_eq!(e.to_string(), "Signal has 5 digits, it needs at least 7 for the message offset"),
+            Ok(message) => panic!("Got message {} from a 5 digit signal", message)
+        }
+
+        // 9999999 is way past the end of 8 digits repeated 10000 times
+        assert!(_q2(parse_signal("99999990").unwrap()).is_err());
+        // and offset 0 is at the start
+        assert!(_q2(parse_signal("00000001").unwrap()).is_err());
+    }
+
+    #[test]
+    fn day16_parse_signal_test() {
+        assert_eq!(
+            parse_signal("1,2,3,4,5,6,7,8").unwrap(),
+            parse_signal("12345678").unwrap()
+        );
+        assert_eq!(
+            parse_signal("1 2, 3\n4").unwrap(),
+            vec![1, 2, 3, 4]
+        );
+        assert!(parse_signal("1,23,4").is_err());
+        assert!(parse_signal("12a4").is_err());
+    }
+
+    #[test]
+    fn day16_stable_test() {
+        // each digit of a 2 digit signal only ever multiplies itself by 1
+        let mut signal = Signal::new(vec![9, 7]);
+        assert_eq!(signal.iterate_until_stable(100).unwrap(), (1, true));
+        assert_eq!(signal.numbers, vec![9, 7]);
+
+        let mut signal = Signal::new(parse_signal("12345678").unwrap());
+        assert_eq!(signal.iterate_until_stable(4).unwrap(), (4, false));
+        assert_eq!(signal.numbers, parse_signal("01029498").unwrap());
+    }
+
+    #[test]
+    fn day16_fft_step_raw_test() {
+        let numbers = vec![1, 2, 3, 4, 5, 6, 7, 8];
+
+        // 1*1 + 3*-1 + 5*1 + 7*-1
+        assert_eq!(fft_step_raw(&numbers, 1), -4);
+        assert_eq!(fft_step_for(&numbers, 1), 4);
+
+        for n in 1..=numbers.len() {
+            assert_eq!(fft_step_raw(&numbers, n).abs() % 10, fft_step_for(&numbers, n));
+        }
+        // 5 + 6 + 7 + 8
+        assert_eq!(fft_step_raw(&numbers, 5), 26);
+    }
 }
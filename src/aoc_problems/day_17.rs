@@ -1,24 +1,21 @@
 use std::cmp;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fs::File;
 use std::fmt;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
+use std::str::FromStr;
 
-type Result<T> = result::Result<T, Box<dyn Error>>;
+use std::collections::{BTreeMap, BTreeSet};
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
+use tile_map::TileMap;
 
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
+type Result<T> = result::Result<T, Box<dyn Error>>;
 
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
@@ -104,6 +101,236 @@ impl fmt::Display for Coordinate {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right
+}
+
+impl Direction {
+    fn new(c: char) -> Option<Direction> {
+        use self::Direction::*;
+        match c {
+            '^' => Some(Up),
+            'v' => Some(Down),
+            '<' => Some(Left),
+            '>' => Some(Right),
+            _ => None
+        }
+    }
+
+    fn to_char(self) -> char {
+        use self::Direction::*;
+        match self {
+            Up => '^',
+            Down => 'v',
+            Left => '<',
+            Right => '>'
+        }
+    }
+
+    fn turn_left(self) -> Direction {
+        use self::Direction::*;
+        match self {
+            Up => Left,
+            Left => Down,
+            Down => Right,
+            Right => Up
+        }
+    }
+
+    fn turn_right(self) -> Direction {
+        use self::Direction::*;
+        match self {
+            Up => Right,
+            Right => Down,
+            Down => Left,
+            Left => Up
+        }
+    }
+
+    // None if it would go off the top or left of the map
+    fn step(self, coord: Coordinate) -> Option<Coordinate> {
+        use self::Direction::*;
+        match self {
+            Up if coord.y > 0 => Some(Coordinate::new(coord.x, coord.y - 1)),
+            Down => Some(Coordinate::new(coord.x, coord.y + 1)),
+            Left if coord.x > 0 => Some(Coordinate::new(coord.x - 1, coord.y)),
+            Right => Some(Coordinate::new(coord.x + 1, coord.y)),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+enum Turn {
+    Left,
+    Right,
+    Straight,
+    Back
+}
+
+// How the robot has to turn to go from facing one way to facing the other. Any direction can
+// be reached from any other, but turning round can't be done with a single L or R, so that's Back.
+fn turn_between(from: Direction, to: Direction) -> Turn {
+    if to == from {
+        Turn::Straight
+    } else if to == from.turn_left() {
+        Turn::Left
+    } else if to == from.turn_right() {
+        Turn::Right
+    } else {
+        Turn::Back
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+enum TileType {
+    Scaffold,
+    Space
+}
+
+// A single camera character; the robot is always standing on scaffold
+impl FromStr for TileType {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<TileType> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some('#'), None) => Ok(TileType::Scaffold),
+            (Some('.'), None) => Ok(TileType::Space),
+            (Some(c), None) if Direction::new(c).is_some() => Ok(TileType::Scaffold),
+            _ => err!("Cannot read camera tile: {:?} should be one of # . ^ v < >", s)
+        }
+    }
+}
+
+// An ASCII code as output by the camera
+impl TryFrom<i64> for TileType {
+    type Error = Box<dyn Error>;
+
+    fn try_from(n: i64) -> Result<TileType> {
+        match u8::try_from(n) {
+            Ok(code) if code.is_ascii() => (code as char).to_string().parse(),
+            _ => err!("Camera output {} isn't an ASCII character", n)
+        }
+    }
+}
+
+struct Scaffolding {
+    tile_map: BTreeMap<Coordinate, TileType>,
+    robot_position: Coordinate,
+    robot_direction: Direction
+}
+
+impl Scaffolding {
+    // The camera view: # is scaffold, . is space and ^v<> is the robot, which is on the scaffold
+    fn new(chars: Vec<Vec<char>>) -> Result<Scaffolding> {
+        let mut tile_map = BTreeMap::new();
+        let mut robot = None;
+
+        for (y, line) in chars.iter().enumerate() {
+            for (x, &c) in line.iter().enumerate() {
+                let coord = Coordinate::new(x, y);
+                let tile_type = match c {
+                    '#' => TileType::Scaffold,
+                    '.' => TileType::Space,
+                    c => match Direction::new(c) {
+                        Some(direction) => {
+                            robot = Some((coord, direction));
+                            TileType::Scaffold
+                        },
+                        None => return err!("Cannot read character: {}", c)
+                    }
+                };
+                tile_map.insert(coord, tile_type);
+            }
+        }
+
+        let (robot_position, robot_direction) = robot.ok_or("Cannot find the robot")?;
+
+        Ok(
+            Scaffolding {
+                tile_map,
+                robot_position,
+                robot_direction
+            }
+        )
+    }
+
+    // Scaffold tiles where the scaffold crosses itself, i.e. with scaffold on all four sides
+    fn crossover_list(&self) -> Vec<Coordinate> {
+        self.tile_map.iter()
+            .filter(|&(_, &tile_type)| tile_type == TileType::Scaffold)
+            .map(|(&coord, _)| coord)
+            .filter(|coord| {
+                coord.neighbours().iter()
+                    .filter(|neighbour| self.tile_map.get(neighbour) == Some(&TileType::Scaffold))
+                    .count() == 4
+            })
+            .collect()
+    }
+
+    // The camera view again, but with each crossover drawn as an O
+    fn render_crossovers(&self) -> String {
+        let mut tile_map: TileMap<char> = TileMap::new(' ', false);
+        for (&coord, &tile_type) in &self.tile_map {
+            let c = if coord == self.robot_position {
+                self.robot_direction.to_char()
+            } else if tile_type == TileType::Scaffold {
+                '#'
+            } else {
+                '.'
+            };
+            tile_map.insert(coord.x as i64, coord.y as i64, c);
+        }
+        for coord in self.crossover_list() {
+            tile_map.insert(coord.x as i64, coord.y as i64, 'O');
+        }
+
+        tile_map.to_string()
+    }
+
+    // Follows a route like ["R", "8", "L", "4"] from start and checks that it stays on the
+    // scaffold the whole way and steps on every scaffold tile at least once
+    fn is_fully_covered(&self, route: &[String], start: Coordinate, direction: Direction) -> bool {
+        let mut position = start;
+        let mut direction = direction;
+
+        let mut visited: BTreeSet<Coordinate> = BTreeSet::new();
+        visited.insert(position);
+
+        for instruction in route {
+            match instruction.as_str() {
+                "L" => direction = direction.turn_left(),
+                "R" => direction = direction.turn_right(),
+                steps => {
+                    let steps: usize = match steps.parse() {
+                        Ok(steps) => steps,
+                        Err(_) => return false
+                    };
+
+                    for _ in 0..steps {
+                        position = match direction.step(position) {
+                            Some(next_position) => next_position,
+                            None => return false
+                        };
+                        if self.tile_map.get(&position) != Some(&TileType::Scaffold) {
+                            return false;
+                        }
+                        visited.insert(position);
+                    }
+                }
+            }
+        }
+
+        self.tile_map.iter()
+            .filter(|&(_, &tile_type)| tile_type == TileType::Scaffold)
+            .all(|(coord, _)| visited.contains(coord))
+    }
+}
 
 pub fn q1(fname: String) -> usize {
     let mut f = File::open(fname).expect("File not found");
@@ -135,3 +362,119 @@ fn _q2(_chars: Vec<Vec<char>>) -> Result<usize> {
     unimplemented!();
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day17_route_coverage_test() {
+        let chars: Vec<Vec<char>> = "
+            #######...#####
+            #.....#...#...#
+            #.....#...#...#
+            ......#...#...#
+            ......#...###.#
+            ......#.....#.#
+            ^########...#.#
+            ......#.#...#.#
+            ......#########
+            ........#...#..
+            ....#########..
+            ....#...#......
+            ....#...#......
+            ....#...#......
+            ....#####......
+        ".trim().lines().map(|l| l.trim().chars().collect()).collect();
+        let scaffolding = Scaffolding::new(chars).unwrap();
+        let start = scaffolding.robot_position;
+        let direction = scaffolding.robot_direction;
+        assert_eq!(start, Coordinate::new(0, 6));
+
+        let route: Vec<String> = "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+            .split(',').map(|s| s.to_string()).collect();
+        assert!(scaffolding.is_fully_covered(&route, start, direction));
+
+        // stopping short leaves the last branch untouched
+        assert!(!scaffolding.is_fully_covered(&route[..route.len()-4], start, direction));
+
+        // turning the wrong way walks off the scaffold
+        let mut wrong_turn = route.clone();
+        wrong_turn[0] = "L".to_string();
+        assert!(!scaffolding.is_fully_covered(&wrong_turn, start, direction));
+    }
+
+    #[test]
+    fn day17_render_crossovers_test() {
+        let chars: Vec<Vec<char>> = "
+            ..#..........
+            ..#..........
+            #######...###
+            #.#...#...#.#
+            #############
+            ..#...#...#..
+            ..#####...^..
+        ".trim().lines().map(|l| l.trim().chars().collect()).collect();
+        let scaffolding = Scaffolding::new(chars).unwrap();
+
+        assert_eq!(
+            scaffolding.crossover_list(),
+            vec![
+                Coordinate::new(2, 2),
+                Coordinate::new(2, 4),
+                Coordinate::new(6, 4),
+                Coordinate::new(10, 4)
+            ]
+        );
+        assert_eq!(
+            scaffolding.render_crossovers(),
+            "..#..........\n\
+             ..#..........\n\
+             ##O####...###\n\
+             #.#...#...#.#\n\
+             ##O###O###O##\n\
+             ..#...#...#..\n\
+             ..#####...^..\n"
+        );
+    }
+
+    #[test]
+    fn day17_turn_between_test() {
+        use self::Direction::*;
+
+        let expected = [
+            (Up, Up, Turn::Straight), (Up, Left, Turn::Left),
+            (Up, Right, Turn::Right), (Up, Down, Turn::Back),
+            (Down, Down, Turn::Straight), (Down, Right, Turn::Left),
+            (Down, Left, Turn::Right), (Down, Up, Turn::Back),
+            (Left, Left, Turn::Straight), (Left, Down, Turn::Left),
+            (Left, Up, Turn::Right), (Left, Right, Turn::Back),
+            (Right, Right, Turn::Straight), (Right, Up, Turn::Left),
+            (Right, Down, Turn::Right), (Right, Left, Turn::Back),
+        ];
+
+        for &(from, to, turn) in expected.iter() {
+            assert_eq!(turn_between(from, to), turn, "turning from {:?} to {:?}", from, to);
+        }
+    }
+
+    #[test]
+    fn day17_parse_tile_test() {
+        assert_eq!("#".parse::<TileType>().unwrap(), TileType::Scaffold);
+        assert_eq!(".".parse::<TileType>().unwrap(), TileType::Space);
+        assert_eq!("^".parse::<TileType>().unwrap(), TileType::Scaffold);
+        assert_eq!(
+            "X".parse::<TileType>().unwrap_err().to_string(),
+            "Cannot read camera tile: \"X\" should be one of # . ^ v < >"
+        );
+        assert!("##".parse::<TileType>().is_err());
+
+        assert_eq!(TileType::try_from(35).unwrap(), TileType::Scaffold);
+        assert_eq!(TileType::try_from(46).unwrap(), TileType::Space);
+        assert_eq!(TileType::try_from(62).unwrap(), TileType::Scaffold);
+        assert!(TileType::try_from(10).is_err());
+        assert_eq!(
+            TileType::try_from(1000).unwrap_err().to_string(),
+            "Camera output 1000 isn't an ASCII character"
+        );
+    }
+}
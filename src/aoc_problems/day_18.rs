@@ -1,38 +1,38 @@
 use std::cmp;
+use std::cmp::Reverse;
 use std::error::Error;
 use std::fs::File;
 use std::fmt;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
 
-use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
+
+use aoc_problems::map_lines;
+use dense_grid::DenseGrid;
 
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
 type GraphEdge = (usize, HashSet<TileType>);
 
-#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
 enum GraphNode {
     Start(Coordinate),
     Key(TileType)
 }
 
-macro_rules! err {
-    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
+impl fmt::Display for GraphNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphNode::Start(_) => write!(f, "@"),
+            GraphNode::Key(key) => write!(f, "{}", key)
+        }
+    }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
@@ -118,7 +118,7 @@ impl fmt::Display for Coordinate {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 enum TileType {
     Open,
     Wall,
@@ -156,21 +156,32 @@ impl fmt::Display for TileType {
 
 struct Vault {
     floor_map: BTreeMap<Coordinate, TileType>,
+    // the same tiles again, which is what the searches look squares up in
+    dense_map: DenseGrid<TileType>,
     current_location: Coordinate,
+    // every @ on the map, in row order
+    starts: Vec<Coordinate>,
     key_locations: HashMap<TileType, Coordinate>,
     dists: HashMap<(GraphNode, GraphNode), (usize, HashSet<TileType>)>,
-    reachable_keys: HashMap<GraphNode, Vec<TileType>>
+    reachable_keys: HashMap<GraphNode, Vec<TileType>>,
+    // edges that have a way round one of the doors on them, so the doors an edge lists aren't
+    // always all needed
+    detours: HashSet<(GraphNode, GraphNode)>
 }
 
 impl Vault {
     fn new(map_lines: Vec<Vec<char>>) -> Result<Vault> {
         let mut floor_map = BTreeMap::new();
         let mut current_location = Coordinate::new(0, 0);
+        let mut starts = vec![];
         let mut key_locations: HashMap<TileType, Coordinate> = HashMap::new();
+        let mut rows: Vec<Vec<TileType>> = vec![];
         for (y, line) in map_lines.iter().enumerate() {
+            let mut row = vec![];
             for (x, &c) in line.iter().enumerate() {
                 if c == '@' {
                     current_location = Coordinate::new(x, y);
+                    starts.push(current_location);
                 }
                 let tile_type = TileType::new(c)?;
                 let coord = Coordinate::new(x, y);
@@ -180,73 +191,37 @@ impl Vault {
                 }
 
                 floor_map.insert(coord, tile_type);
+                row.push(tile_type);
             }
+            rows.push(row);
         }
 
         Ok(
             Vault {
                 floor_map,
+                dense_map: DenseGrid::from_rows(rows)?,
                 current_location,
+                starts,
                 key_locations,
                 dists: HashMap::new(),
-                reachable_keys: HashMap::new()
+                reachable_keys: HashMap::new(),
+                detours: HashSet::new()
             }
         )
     }
 
-    fn all_reachable_keys(&mut self, keys: &Vec<TileType>) -> Result<Vec<TileType>> {
-        // let mut d = BTreeMap::new();
-        // d.insert(self.current_location, 0);
-
-        let mut queue: VecDeque<Coordinate> = VecDeque::new();
-        queue.push_front(self.current_location);
-
-        let mut todo_set: BTreeSet<Coordinate> = BTreeSet::new();
-        let mut visited: BTreeSet<Coordinate> = BTreeSet::new();
-
-        let mut reachable_keys: Vec<TileType> = vec![];
-
-        while let Some(c) = queue.pop_front() {
-            todo_set.remove(&c);
-            visited.insert(c);
-
-            for neighbour in c.adjacent_squares().into_iter().filter(|coord| self.floor_map.get(&coord) != Some(&TileType::Wall)) {
-                // Don't add to squares to do
-                if visited.contains(&neighbour) {
-                    continue;
-                }
-
-                // Don't add square if it's a door and we don't have the right key
-                if let Some(TileType::Door(c)) = self.floor_map.get(&neighbour) {
-                    if !keys.contains(&TileType::Key(*c)) {
-                        continue;
-                    }
-                }
-
-                // Add key to reachable keys if we don't already have it
-                if let Some(TileType::Key(c)) = self.floor_map.get(&neighbour) {
-                    if !keys.contains(&TileType::Key(*c)) {
-                        reachable_keys.push(TileType::Key(*c));
-                        continue;
-                    }
-                }
-
-                if !todo_set.contains(&neighbour) {
-                    queue.push_back(neighbour);
-                    todo_set.insert(neighbour);
-                }
-
-                // let new_dist = 1 + *d.get(&c).unwrap_or(&0);
-                // if !d.contains_key(&neighbour) || new_dist < d[&neighbour] {
-                //     d.insert(neighbour, new_dist);
-                // }
-            }
-        }
+    // None off the edge of the map
+    fn tile_at(&self, coord: &Coordinate) -> Option<&TileType> {
+        self.dense_map.get(coord.x, coord.y)
+    }
 
-        Ok(reachable_keys)
+    // The map as it was read in, with the starting point as @
+    fn grid_string(&self) -> String {
+        self.to_string()
     }
 
-    fn distance(&self, from: Coordinate, to: Coordinate) -> usize {
+    // Shortest walk from one square to another that only goes through doors whose key is in keys
+    fn distance(&self, from: Coordinate, to: Coordinate, keys: &[TileType]) -> Result<usize> {
         let mut d = BTreeMap::new();
         d.insert(from, 0);
 
@@ -264,14 +239,16 @@ impl Vault {
                 break;
             }
 
-            for neighbour in c.adjacent_squares().into_iter().filter(|coord| self.floor_map.get(&coord) != Some(&TileType::Wall)) {
+            for neighbour in c.adjacent_squares().into_iter().filter(|coord| self.tile_at(coord) != Some(&TileType::Wall)) {
                 // Don't add to squares to do
                 if visited.contains(&neighbour) {
                     continue;
                 }
 
-                if let Some(TileType::Key(_c)) = self.floor_map.get(&neighbour) {
-                    continue;
+                if let Some(TileType::Door(c)) = self.tile_at(&neighbour) {
+                    if !keys.contains(&TileType::Key(*c)) {
+                        continue;
+                    }
                 }
 
                 if !todo_set.contains(&neighbour) {
@@ -286,9 +263,15 @@ impl Vault {
             }
         }
 
-        d[&to]
+        match d.get(&to) {
+            Some(&dist) => Ok(dist),
+            None => err!("Cannot get from {} to {} with keys {:?}", from, to, keys)
+        }
     }
 
+    // The keys that can be walked to from pt, doors or not. The search stops at each key rather
+    // than walking over it, so a key that's only reachable by going past another one isn't
+    // included: the graph only gets to it through the key in front.
     fn keys_reachable_from(&self, pt: Coordinate) -> Result<Vec<TileType>> {
         let mut queue: VecDeque<Coordinate> = VecDeque::new();
         queue.push_front(pt);
@@ -302,15 +285,18 @@ impl Vault {
             todo_set.remove(&c);
             visited.insert(c);
 
-            for neighbour in c.adjacent_squares().into_iter().filter(|coord| self.floor_map.get(&coord) != Some(&TileType::Wall)) {
+            for neighbour in c.adjacent_squares().into_iter().filter(|coord| self.tile_at(coord) != Some(&TileType::Wall)) {
                 // Don't add to squares to do
                 if visited.contains(&neighbour) {
                     continue;
                 }
 
                 // don't walk past a key, as that's the end of the line
-                if let Some(TileType::Key(c)) = self.floor_map.get(&neighbour) {
-                    keys.push(TileType::Key(*c));
+                // (it can be next to more than one searched square, so only add it once)
+                if let Some(TileType::Key(c)) = self.tile_at(&neighbour) {
+                    if !keys.contains(&TileType::Key(*c)) {
+                        keys.push(TileType::Key(*c));
+                    }
                     continue;
                 }
 
@@ -342,14 +328,17 @@ impl Vault {
                 break;
             }
 
-            for neighbour in c.adjacent_squares().into_iter().filter(|coord| self.floor_map.get(&coord) != Some(&TileType::Wall)) {
+            for neighbour in c.adjacent_squares().into_iter().filter(|coord| self.tile_at(coord) != Some(&TileType::Wall)) {
                 // Don't add to squares to do
                 if visited.contains(&neighbour) {
                     continue;
                 }
 
-                if let Some(TileType::Key(_c)) = self.floor_map.get(&neighbour) {
-                    continue;
+                // other keys are the end of the line for an edge
+                if let Some(TileType::Key(_c)) = self.tile_at(&neighbour) {
+                    if neighbour != to {
+                        continue;
+                    }
                 }
 
                 if !todo_set.contains(&neighbour) {
@@ -377,7 +366,7 @@ impl Vault {
                 break;
             }
 
-            if let Some(TileType::Door(c)) = self.floor_map.get(&previous_coord) {
+            if let Some(TileType::Door(c)) = self.tile_at(&previous_coord) {
                 doors.insert(TileType::Key(*c));
             }
             current_coordinate = previous_coord;
@@ -386,19 +375,45 @@ impl Vault {
         (d[&to], doors)
     }
 
+    // Distance between two graph nodes, and the keys needed for the doors in between
+    fn edge(&self, from: GraphNode, to: GraphNode) -> Option<&GraphEdge> {
+        self.dists.get(&(from, to))
+    }
+
+    // The key graph as CSV, one row per edge: from,to,distance,doors in the way (e.g. AC)
+    fn export_graph(&self) -> String {
+        let mut rows: Vec<String> = self.dists.iter().map(|(&(from, to), &(distance, ref doors))| {
+            let mut door_names: Vec<String> = doors.iter().map(|key| key.to_string().to_uppercase()).collect();
+            door_names.sort();
+
+            format!("{},{},{},{}", from, to, distance, door_names.join(""))
+        }).collect();
+        rows.sort();
+
+        let mut export = "from,to,distance,doors\n".to_string();
+        for row in rows {
+            export.push_str(&row);
+            export.push('\n');
+        }
+
+        export
+    }
+
     fn generate_key_graph(&mut self) -> Result<()> {
         // first, add path from start to all reachable keys
         // then path from each key to all others
 
-        // first, add everything reachable from the starting point
-        let keys_from_start = self.keys_reachable_from(self.current_location)?;
-        for key in &keys_from_start {
-            self.dists.insert(
-                (GraphNode::Start(self.current_location), GraphNode::Key(*key)),
-                self.graph_edge_for(self.current_location, self.key_locations[&key])
-            );
+        // first, add everything reachable from each starting point
+        for start in self.starts.clone() {
+            let keys_from_start = self.keys_reachable_from(start)?;
+            for key in &keys_from_start {
+                self.dists.insert(
+                    (GraphNode::Start(start), GraphNode::Key(*key)),
+                    self.graph_edge_for(start, self.key_locations[key])
+                );
+            }
+            self.reachable_keys.insert(GraphNode::Start(start), keys_from_start);
         }
-        self.reachable_keys.insert(GraphNode::Start(self.current_location), keys_from_start);
 
         for (key, key_coordinate) in self.key_locations.iter() {
             let possible_targets = self.keys_reachable_from(*key_coordinate)?;
@@ -412,85 +427,108 @@ impl Vault {
             self.reachable_keys.insert(GraphNode::Key(*key), possible_targets);
         }
 
+        // an edge has a detour if it can still be walked with one of its doors shut
+        let all_keys: Vec<TileType> = self.key_locations.keys().cloned().collect();
+        for (&(from, to), (_, doors)) in self.dists.iter() {
+            let has_detour = doors.iter().any(|door| {
+                let keys: Vec<TileType> = all_keys.iter().filter(|&key| key != door).cloned().collect();
+                self.distance(self.node_location(from), self.node_location(to), &keys).is_ok()
+            });
+            if has_detour {
+                self.detours.insert((from, to));
+            }
+        }
+
         Ok(())
     }
 
-    fn path_from_to(&self, from: Coordinate, to: Coordinate) -> (usize, HashSet<TileType>) {
-        let mut d = BTreeMap::new();
-        d.insert(from, 0);
-
-        let mut queue: VecDeque<Coordinate> = VecDeque::new();
-        queue.push_front(from);
-
-        let mut todo_set: BTreeSet<Coordinate> = BTreeSet::new();
-        let mut visited: BTreeSet<Coordinate> = BTreeSet::new();
-
-        while let Some(c) = queue.pop_front() {
-            todo_set.remove(&c);
-            visited.insert(c);
+    fn node_location(&self, node: GraphNode) -> Coordinate {
+        match node {
+            GraphNode::Start(location) => location,
+            GraphNode::Key(key) => self.key_locations[&key]
+        }
+    }
 
-            if c == to {
-                break;
+    // Fewest steps for a robot on each of the starts to pick up every key between them, where a
+    // door is open for all the robots once any of them has its key. This is Dijkstra over
+    // (where each robot is, keys picked up so far), moving one robot along one key graph edge at
+    // a time. Robots can go back to keys they already have, which is how they get past them.
+    fn fewest_steps_for_all_keys(&self, starts: &[Coordinate]) -> Result<usize> {
+        let start_state: (Vec<GraphNode>, BTreeSet<TileType>) = (
+            starts.iter().map(|&start| GraphNode::Start(start)).collect(),
+            BTreeSet::new()
+        );
+
+        let mut best_steps = HashMap::new();
+        best_steps.insert(start_state.clone(), 0);
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0, start_state)));
+
+        while let Some(Reverse((steps, state))) = queue.pop() {
+            if state.1.len() == self.key_locations.len() {
+                return Ok(steps);
             }
 
-            for neighbour in c.adjacent_squares().into_iter().filter(|coord| self.floor_map.get(&coord) != Some(&TileType::Wall)) {
-                // Don't add to squares to do
-                if visited.contains(&neighbour) {
-                    continue;
-                }
-
-                if !todo_set.contains(&neighbour) {
-                    queue.push_back(neighbour);
-                    todo_set.insert(neighbour);
-                }
-
-                let new_dist = 1 + *d.get(&c).unwrap_or(&0);
-                if !d.contains_key(&neighbour) || new_dist < d[&neighbour] {
-                    d.insert(neighbour, new_dist);
-                }
+            // already got here in fewer steps
+            if best_steps.get(&state).is_some_and(|&best| best < steps) {
+                continue;
             }
-        }
 
-        // now distance has been found, backtrace to find out what doors we pass through
-        let mut current_square = to;
-        let mut doors: HashSet<TileType> = HashSet::new();
-        loop {
-            let mut min_dist = std::usize::MAX;
-            for neighbour in current_square.adjacent_squares().into_iter().filter(|coord| self.floor_map.get(&coord) != Some(&TileType::Wall)) {
-                if let Some(TileType::Door(c)) = self.floor_map.get(&neighbour) {
-                    doors.insert(TileType::Key(*c));
-                }
-                if d.contains_key(&neighbour) && d[&neighbour] < min_dist {
-                    min_dist = d[&neighbour];
-                    current_square = neighbour;
+            let (positions, keys) = &state;
+            for (robot, position) in positions.iter().enumerate() {
+                for &key in self.reachable_keys.get(position).into_iter().flatten() {
+                    let distance = match self.edge(*position, GraphNode::Key(key)) {
+                        Some((distance, doors)) if doors.iter().all(|door| keys.contains(door)) => *distance,
+                        // the edge is the shortest way, which can be through a locked door when
+                        // there's a longer way round
+                        Some(_) if self.detours.contains(&(*position, GraphNode::Key(key))) => {
+                            let held: Vec<TileType> = keys.iter().cloned().collect();
+                            match self.distance(self.node_location(*position), self.key_locations[&key], &held) {
+                                Ok(distance) => distance,
+                                Err(_) => continue
+                            }
+                        },
+                        Some(_) => continue,
+                        None => return err!("No edge from {} to {}", position, key)
+                    };
+
+                    let mut new_positions = positions.clone();
+                    new_positions[robot] = GraphNode::Key(key);
+                    let mut new_keys = keys.clone();
+                    new_keys.insert(key);
+
+                    let new_state = (new_positions, new_keys);
+                    let new_steps = steps + distance;
+                    match best_steps.get(&new_state) {
+                        Some(&best) if best <= new_steps => {},
+                        _ => {
+                            best_steps.insert(new_state.clone(), new_steps);
+                            queue.push(Reverse((new_steps, new_state)));
+                        }
+                    }
                 }
             }
-            if current_square == from {
-                break;
-            }
         }
 
-        (d[&to], doors)
-    }
-
-    fn total_steps_for_keys(&self, path: &Vec<TileType>) -> usize {
-        let mut order = vec![self.current_location];
-        order.extend(path.iter().map(|&key| self.key_locations[&key]));
-
-        order.windows(2).map(|t| self.distance(t[0], t[1])).sum()
+        err!("maze has unreachable keys")
     }
 }
 
+// One line per row of the map, each ending in a newline
 impl fmt::Display for Vault {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut current_y = 0;
+        // floor_map is in row order, so a row ends whenever y changes
+        let mut current_y = None;
         for (c, tile) in self.floor_map.iter() {
-            if c.y != current_y {
-                write!(f, "{}", '\n')?;
-                current_y = c.y;
+            if current_y.is_some() && current_y != Some(c.y) {
+                writeln!(f)?;
             }
+            current_y = Some(c.y);
             write!(f, "{}", tile)?;
         }
+        if current_y.is_some() {
+            writeln!(f)?;
+        }
         Ok(())
     }
 }
@@ -501,9 +539,16 @@ pub fn q1(fname: String) -> usize {
 
     f.read_to_string(&mut f_contents).expect("Couldn't find file");
 
-    let map_lines: Vec<Vec<char>> = f_contents.trim().lines().map(|s| s.trim().chars().collect()).collect();
+    q1_from_str(&f_contents).unwrap()
+}
 
-    _q1(map_lines).unwrap()
+// Splits a map into rows of characters, ignoring blank lines around it and indentation
+fn parse_map(map: &str) -> Vec<Vec<char>> {
+    map_lines(map).into_iter().map(|s| s.trim().chars().collect()).collect()
+}
+
+fn q1_from_str(map: &str) -> Result<usize> {
+    _q1(parse_map(map))
 }
 
 fn _q1(chars: Vec<Vec<char>>) -> Result<usize> {
@@ -511,37 +556,8 @@ fn _q1(chars: Vec<Vec<char>>) -> Result<usize> {
 
     vault.generate_key_graph()?;
 
-    let mut potential_key_orderings: Vec<Vec<TileType>> = vec![vec![]];
-    loop {
-        let mut new_keys = false;
-        let mut new_key_orderings: Vec<Vec<TileType>> = vec![];
-        for key_list in &potential_key_orderings {
-            let available_keys = vault.all_reachable_keys(key_list)?;
-            println!("All available keys with {:?} is {:?}", key_list, available_keys);
-            if !available_keys.is_empty() {
-                new_keys = true;
-            }
-            for key in available_keys {
-                let mut old_list = key_list.clone();
-                old_list.push(key);
-                new_key_orderings.push(old_list);
-            }
-        }
-
-        if !new_keys {
-            break;
-        }
-
-        potential_key_orderings = new_key_orderings;
-    }
-
-    println!("Number of potential orderings = {}", potential_key_orderings.len());
-
-    let timings: Vec<usize> = potential_key_orderings.iter()
-        .map(|path| vault.total_steps_for_keys(path))
-        .collect();
-
-    Ok(*timings.iter().min().unwrap())
+    let start = vault.current_location;
+    vault.fewest_steps_for_all_keys(&[start])
 }
 
 pub fn q2(fname: String) -> usize {
@@ -550,13 +566,55 @@ pub fn q2(fname: String) -> usize {
 
     f.read_to_string(&mut f_contents).expect("Couldn't find file");
 
-    let map_lines: Vec<Vec<char>> = f_contents.trim().lines().map(|s| s.trim().chars().collect()).collect();
+    _q2(parse_map(&f_contents)).unwrap()
+}
+
+// Replaces the 3x3 around a lone @ with four robots walled off from each other:
+//   ...      @#@
+//   .@.  ->  ###
+//   ...      @#@
+// A map that already has four @s is left alone.
+fn split_vault(chars: &mut [Vec<char>]) -> Result<()> {
+    let starts: Vec<(usize, usize)> = chars.iter()
+        .enumerate()
+        .flat_map(|(y, row)| row.iter().enumerate().filter(|&(_, &c)| c == '@').map(move |(x, _)| (x, y)))
+        .collect();
+
+    let (x, y) = match starts.len() {
+        4 => return Ok(()),
+        1 => starts[0],
+        n => return err!("Expected one @ to split into four, found {}", n)
+    };
+
+    if x == 0 || y == 0 {
+        return err!("@ at ({}, {}) is on the edge of the map", x, y);
+    }
+    let replacement = ["@#@", "###", "@#@"];
+    for (dy, replacement_row) in replacement.iter().enumerate() {
+        for (dx, new_c) in replacement_row.chars().enumerate() {
+            let square = chars.get_mut(y + dy - 1).and_then(|row| row.get_mut(x + dx - 1));
+            match square {
+                Some(c) if *c == '.' || *c == '@' => *c = new_c,
+                _ => return err!("The 3x3 around the @ at ({}, {}) isn't all open", x, y)
+            }
+        }
+    }
 
-    _q2(map_lines).unwrap()
+    Ok(())
 }
 
-fn _q2(_chars: Vec<Vec<char>>) -> Result<usize> {
-    unimplemented!();
+fn q2_from_str(map: &str) -> Result<usize> {
+    _q2(parse_map(map))
+}
+
+fn _q2(mut chars: Vec<Vec<char>>) -> Result<usize> {
+    split_vault(&mut chars)?;
+
+    let mut vault = Vault::new(chars)?;
+    vault.generate_key_graph()?;
+
+    let starts = vault.starts.clone();
+    vault.fewest_steps_for_all_keys(&starts)
 }
 
 #[cfg(test)]
@@ -565,53 +623,53 @@ mod tests {
 
     #[test]
     fn day18_q1_test1() {
-        let map: Vec<Vec<char>> = "
+        let map = "
         #########
         #b.A.@.a#
         #########
-        ".trim().lines().map(|line| line.trim().chars().collect()).collect();
+        ";
 
         assert_eq!(
-            _q1(map).unwrap(),
+            q1_from_str(map).unwrap(),
             8
         )
     }
 
     #[test]
     fn day18_q1_test2() {
-        let map: Vec<Vec<char>> = "
+        let map = "
         ########################
         #f.D.E.e.C.b.A.@.a.B.c.#
         ######################.#
         #d.....................#
         ########################
-        ".trim().lines().map(|line| line.trim().chars().collect()).collect();
+        ";
 
         assert_eq!(
-            _q1(map).unwrap(),
+            q1_from_str(map).unwrap(),
             86
         )
     }
 
     #[test]
     fn day18_q1_test3() {
-        let map: Vec<Vec<char>> = "
+        let map = "
         ########################
         #...............b.C.D.f#
         #.######################
         #.....@.a.B.c.d.A.e.F.g#
         ########################
-        ".trim().lines().map(|line| line.trim().chars().collect()).collect();
+        ";
 
         assert_eq!(
-            _q1(map).unwrap(),
+            q1_from_str(map).unwrap(),
             132
         )
     }
 
     #[test]
     fn day18_q1_test4() {
-        let map: Vec<Vec<char>> = "
+        let map = "
         #################
         #i.G..c...e..H.p#
         ########.########
@@ -621,28 +679,315 @@ mod tests {
         ########.########
         #l.F..d...h..C.m#
         #################
-        ".trim().lines().map(|line| line.trim().chars().collect()).collect();
+        ";
 
         assert_eq!(
-            _q1(map).unwrap(),
+            q1_from_str(map).unwrap(),
             136
         )
     }
 
     #[test]
     fn day18_q1_test5() {
-        let map: Vec<Vec<char>> = "
+        let map = "
         ########################
         #@..............ac.GI.b#
         ###d#e#f################
         ###A#B#C################
         ###g#h#i################
         ########################
-        ".trim().lines().map(|line| line.trim().chars().collect()).collect();
+        ";
 
         assert_eq!(
-            _q1(map).unwrap(),
+            q1_from_str(map).unwrap(),
             81
         )
     }
+
+    #[test]
+    fn day18_edge_test() {
+        let map = "
+        #########
+        #b.A.@.a#
+        #########
+        ";
+
+        let mut vault = Vault::new(parse_map(map)).unwrap();
+        vault.generate_key_graph().unwrap();
+
+        let start = GraphNode::Start(vault.current_location);
+        assert_eq!(
+            vault.edge(start, GraphNode::Key(TileType::Key('a'))),
+            Some(&(2, HashSet::new()))
+        );
+
+        let mut door_a: HashSet<TileType> = HashSet::new();
+        door_a.insert(TileType::Key('a'));
+        assert_eq!(
+            vault.edge(GraphNode::Key(TileType::Key('a')), GraphNode::Key(TileType::Key('b'))),
+            Some(&(6, door_a))
+        );
+        assert_eq!(vault.edge(start, GraphNode::Start(vault.current_location)), None);
+    }
+
+    #[test]
+    fn day18_export_graph_test() {
+        let map = "
+        #########
+        #b.A.@.a#
+        #########
+        ";
+
+        let mut vault = Vault::new(parse_map(map)).unwrap();
+        vault.generate_key_graph().unwrap();
+
+        assert_eq!(
+            vault.export_graph(),
+            "from,to,distance,doors\n@,a,2,\n@,b,4,A\na,b,6,A\nb,a,6,A\n"
+        );
+    }
+
+    #[test]
+    fn day18_keys_behind_keys_test() {
+        let map = "
+        #########
+        #@.a.c.b#
+        ###.#####
+        #########
+        ";
+
+        let mut vault = Vault::new(parse_map(map)).unwrap();
+        vault.generate_key_graph().unwrap();
+        let start = GraphNode::Start(vault.current_location);
+
+        // c is only reachable by first picking up a
+        assert_eq!(vault.reachable_keys[&start], vec![TileType::Key('a')]);
+        assert_eq!(vault.edge(start, GraphNode::Key(TileType::Key('c'))), None);
+        assert_eq!(
+            vault.reachable_keys[&GraphNode::Key(TileType::Key('a'))],
+            vec![TileType::Key('c')]
+        );
+        assert_eq!(q1_from_str(map).unwrap(), 6);
+
+        // a key next to two of the searched squares only turns up once
+        let map = "
+        #####
+        #.a.#
+        #.#.#
+        #.@.#
+        #####
+        ";
+
+        let mut vault = Vault::new(parse_map(map)).unwrap();
+        vault.generate_key_graph().unwrap();
+        assert_eq!(
+            vault.reachable_keys[&GraphNode::Start(vault.current_location)],
+            vec![TileType::Key('a')]
+        );
+    }
+
+    #[test]
+    fn day18_grid_string_test() {
+        let map = "
+        #########
+        #b.A.@.a#
+        #########
+        ";
+
+        let vault = Vault::new(parse_map(map)).unwrap();
+        assert_eq!(
+            vault.grid_string(),
+            "#########\n#b.A.@.a#\n#########\n"
+        );
+
+        let empty_vault = Vault::new(vec![]).unwrap();
+        assert_eq!(empty_vault.grid_string(), "");
+    }
+
+    #[test]
+    fn day18_doors_in_the_way_test() {
+        let map = "
+        #######
+        #@.A.a#
+        #.###.#
+        #.....#
+        #######
+        ";
+
+        let mut vault = Vault::new(parse_map(map)).unwrap();
+        vault.generate_key_graph().unwrap();
+        let start = vault.current_location;
+        let a_location = vault.key_locations[&TileType::Key('a')];
+
+        // straight through the door is 4, but without its key the way round is 8
+        assert_eq!(vault.distance(start, a_location, &[TileType::Key('a')]).unwrap(), 4);
+        assert_eq!(vault.distance(start, a_location, &[]).unwrap(), 8);
+
+        // the graph only has the short way, so the search has to find the detour itself
+        let edge = (GraphNode::Start(start), GraphNode::Key(TileType::Key('a')));
+        assert_eq!(vault.edge(edge.0, edge.1).map(|&(distance, _)| distance), Some(4));
+        assert!(vault.detours.contains(&edge));
+        assert_eq!(vault.fewest_steps_for_all_keys(&[start]).unwrap(), 8);
+
+        let mut walled_in = Vault::new(parse_map("
+        #####
+        #@Aa#
+        #####
+        ")).unwrap();
+        walled_in.generate_key_graph().unwrap();
+        let start = walled_in.current_location;
+        assert!(walled_in.fewest_steps_for_all_keys(&[start]).is_err());
+    }
+
+    #[test]
+    fn day18_unreachable_keys_test() {
+        let map = "
+        #########
+        #b.@.A.a#
+        #########
+        ";
+
+        match q1_from_str(map) {
+            Err(e) => assert_eq!(e.to_string(), "maze has unreachable keys"),
+            Ok(steps) => panic!("Got {} steps for a maze that can't be finished", steps)
+        }
+    }
+
+    #[test]
+    fn day18_q1_from_str_test() {
+        assert_eq!(
+            q1_from_str("#########\n#b.A.@.a#\n#########").unwrap(),
+            8
+        );
+    }
+
+    #[test]
+    fn day18_dense_map_traversal_test() {
+        let map = "
+        ########################
+        #@..............ac.GI.b#
+        ###d#e#f################
+        ###A#B#C################
+        ###g#h#i################
+        ########################
+        ";
+
+        let vault = Vault::new(parse_map(map)).unwrap();
+
+        // breadth first over every open square, looking tiles up with lookup
+        let traversal = |lookup: &dyn Fn(&Coordinate) -> Option<TileType>| {
+            let mut order = vec![vault.current_location];
+            let mut queue = VecDeque::from(vec![vault.current_location]);
+            while let Some(c) = queue.pop_front() {
+                for neighbour in c.adjacent_squares().into_iter().filter(|coord| lookup(coord) != Some(TileType::Wall)) {
+                    if !order.contains(&neighbour) {
+                        order.push(neighbour);
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+            order
+        };
+
+        let from_btree = traversal(&|coord| vault.floor_map.get(coord).cloned());
+        let from_dense = traversal(&|coord| vault.tile_at(coord).cloned());
+
+        assert_eq!(from_btree.len(), vault.floor_map.values().filter(|&&tile| tile != TileType::Wall).count());
+        assert_eq!(from_btree, from_dense);
+    }
+
+    #[test]
+    fn day18_q2_test1() {
+        let map = "
+        #######
+        #a.#Cd#
+        ##...##
+        ##.@.##
+        ##...##
+        #cB#Ab#
+        #######
+        ";
+
+        assert_eq!(q2_from_str(map).unwrap(), 8);
+    }
+
+    #[test]
+    fn day18_q2_test2() {
+        let map = "
+        ###############
+        #d.ABC.#.....a#
+        ######@#@######
+        ###############
+        ######@#@######
+        #b.....#.....c#
+        ###############
+        ";
+
+        assert_eq!(q2_from_str(map).unwrap(), 24);
+    }
+
+    #[test]
+    fn day18_q2_test3() {
+        let map = "
+        #############
+        #DcBa.#.GhKl#
+        #.###@#@#I###
+        #e#d#####j#k#
+        ###C#@#@###J#
+        #fEbA.#.FgHi#
+        #############
+        ";
+
+        assert_eq!(q2_from_str(map).unwrap(), 32);
+    }
+
+    #[test]
+    fn day18_q2_test4() {
+        let map = "
+        #############
+        #g#f.D#..h#l#
+        #F###e#E###.#
+        #dCba@#@BcIJ#
+        #############
+        #nK.L@#@G...#
+        #M###N#H###.#
+        #o#m..#i#jk.#
+        #############
+        ";
+
+        assert_eq!(q2_from_str(map).unwrap(), 72);
+    }
+
+    #[test]
+    fn day18_split_vault_test() {
+        let mut chars = parse_map("
+        #######
+        #a.#Cd#
+        ##...##
+        ##.@.##
+        ##...##
+        #cB#Ab#
+        #######
+        ");
+        split_vault(&mut chars).unwrap();
+
+        assert_eq!(
+            Vault::new(chars.clone()).unwrap().grid_string(),
+            "#######\n#a.#Cd#\n##@#@##\n#######\n##@#@##\n#cB#Ab#\n#######\n"
+        );
+
+        // already split, so nothing changes
+        let split = chars.clone();
+        split_vault(&mut chars).unwrap();
+        assert_eq!(chars, split);
+
+        let mut crowded = parse_map("
+        #####
+        #.#.#
+        #.@.#
+        #...#
+        #####
+        ");
+        assert!(split_vault(&mut crowded).is_err());
+    }
 }
@@ -2,7 +2,6 @@ use std::cmp;
 use std::error::Error;
 use std::fs::File;
 use std::fmt;
-use std::io;
 use std::io::prelude::*;
 use std::ops::{Add, Sub, AddAssign};
 use std::result;
@@ -11,24 +10,15 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 
 use itertools::Itertools;
 
+use aoc_problems::char_grid;
+use debug;
+
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
 macro_rules! err {
     ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
 }
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
-
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
-}
-
 #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
 struct Coordinate {
     x: usize,
@@ -55,6 +45,14 @@ impl Coordinate {
 
         result
     }
+
+    // Same as neighbours, but only those inside a width x height grid
+    fn neighbours_within(&self, width: usize, height: usize) -> Vec<Coordinate> {
+        self.neighbours()
+            .into_iter()
+            .filter(|coord| coord.x < width && coord.y < height)
+            .collect()
+    }
 }
 
 impl Ord for Coordinate {
@@ -140,17 +138,29 @@ struct PlutoMaze {
     outside_portals: BTreeSet<Coordinate>,
     starting_position: Coordinate,
     end_position: Coordinate,
-    end_distance: usize
+    end_distance: usize,
+    // deepest recursion level on the last path found
+    max_depth: usize
 }
 
 impl PlutoMaze {
     fn new(chars: Vec<Vec<char>>) -> Result<PlutoMaze> {
-        println!("Size of input = {}", chars.len());
-        println!("Size of line = {:?}", chars.iter().map(|line| line.len()).collect::<Vec<_>>());
+        if debug::verbose() {
+            eprintln!("Size of input = {}", chars.len());
+            eprintln!("Size of line = {:?}", chars.iter().map(|line| line.len()).collect::<Vec<_>>());
+        }
         let mut tile_map: BTreeMap<Coordinate, TileType> = BTreeMap::new();
         let mut portal_map: HashMap<TileType, Vec<Coordinate>> = HashMap::new();
         let mut inside_portals: BTreeSet<Coordinate> = BTreeSet::new();
         let mut outside_portals: BTreeSet<Coordinate> = BTreeSet::new();
+
+        // lines can be ragged, so a coordinate inside the grid may still be past the end of its line
+        let height = chars.len();
+        let width = chars.iter().map(|line| line.len()).max().unwrap_or(0);
+        let is_letter = |coord: &Coordinate| {
+            chars[coord.y].get(coord.x).is_some_and(|c| c.is_ascii_uppercase())
+        };
+
         for (y, line) in chars.iter().enumerate() {
             for (x, &c) in line.iter().enumerate() {
                 // println!("Looking at coordinate ({}, {})", x, y);
@@ -159,21 +169,15 @@ impl PlutoMaze {
                         tile_map.insert(Coordinate::new(x, y), TileType::Wall);
                     },
                     '.' => {
-                        if let Some(first_letter_coord) = Coordinate::new(x, y).neighbours().into_iter()
-                            .filter(|coord| {
-                                coord.y < chars.len()
-                                    && coord.x < chars[coord.y].len()
-                                    && chars[coord.y][coord.x].is_ascii_uppercase()
-                        }).next() {
+                        if let Some(first_letter_coord) = Coordinate::new(x, y)
+                            .neighbours_within(width, height)
+                            .into_iter()
+                            .find(|coord| is_letter(coord)) {
                             // this is a portal square
                             let second_letter_coord = first_letter_coord
-                                .neighbours()
+                                .neighbours_within(width, height)
                                 .into_iter()
-                                .filter(|coord| {
-                                    coord.y < chars.len()
-                                        && coord.x < chars[coord.y].len()
-                                        && chars[coord.y][coord.x].is_ascii_uppercase()
-                                }).next()
+                                .find(|coord| is_letter(coord))
                                 .ok_or("Cannot find second portal character")?;
 
                             // second letter coord is on the outside of the map if outside portal
@@ -230,7 +234,8 @@ impl PlutoMaze {
                 inside_portals,
                 starting_position,
                 end_position,
-                end_distance: 0
+                end_distance: 0,
+                max_depth: 0
             }
         )
     }
@@ -310,9 +315,38 @@ impl PlutoMaze {
         Ok(neighbours)
     }
 
+    // Flood fill from AA without any recursion, which is enough to spot a maze where ZZ can
+    // never be reached before spending time on the full search
+    fn check_connected(&self) -> Result<()> {
+        let mut seen: BTreeSet<Coordinate> = BTreeSet::new();
+        seen.insert(self.starting_position);
+        let mut to_visit = vec![self.starting_position];
+
+        while let Some(coord) = to_visit.pop() {
+            if coord == self.end_position {
+                return Ok(());
+            }
+
+            for (neighbour, _) in self.adjacent_tiles((coord, 0), false)? {
+                if seen.insert(neighbour) {
+                    to_visit.push(neighbour);
+                }
+            }
+        }
+
+        err!(
+            "AA at {} cannot reach ZZ at {}: they're in separate parts of the maze",
+            self.starting_position, self.end_position
+        )
+    }
+
     fn find_path_through_maze(&mut self, recursive: bool) -> Result<()> {
+        self.check_connected()?;
+
         let mut d = BTreeMap::new();
         d.insert((self.starting_position, 0), 0);
+        // where each square was first reached from, to walk the shortest path back afterwards
+        let mut previous: BTreeMap<(Coordinate, usize), (Coordinate, usize)> = BTreeMap::new();
 
         let mut queue: VecDeque<(Coordinate, usize)> = VecDeque::new();
         queue.push_front((self.starting_position, 0));
@@ -342,14 +376,38 @@ impl PlutoMaze {
                 let new_dist = 1 + *d.get(&c).unwrap_or(&0);
                 if !d.contains_key(&neighbour) || new_dist < d[&neighbour] {
                     d.insert(neighbour, new_dist);
+                    previous.insert(neighbour, c);
                 }
             }
         }
 
         self.end_distance = *d.get(&(self.end_position, 0)).ok_or("End position not in distance map")?;
 
+        let mut position = (self.end_position, 0);
+        self.max_depth = 0;
+        while let Some(&previous_position) = previous.get(&position) {
+            self.max_depth = cmp::max(self.max_depth, previous_position.1);
+            position = previous_position;
+        }
+
         Ok(())
     }
+
+    // The deepest recursion level the last path found goes down to; always 0 without recursion
+    fn max_depth_reached(&self) -> usize {
+        self.max_depth
+    }
+
+    // (flat distance, recursive distance) from one parse of the maze
+    fn solve(&mut self) -> Result<(usize, usize)> {
+        self.find_path_through_maze(false)?;
+        let flat_distance = self.end_distance;
+
+        self.find_path_through_maze(true)?;
+        let recursive_distance = self.end_distance;
+
+        Ok((flat_distance, recursive_distance))
+    }
 }
 
 impl fmt::Display for PlutoMaze {
@@ -373,16 +431,20 @@ pub fn q1(fname: String) -> usize {
 
     f.read_to_string(&mut f_contents).expect("Couldn't find file");
 
-    let chars: Vec<Vec<char>> = f_contents.split('\n').filter(|s| s.len() > 0).map(|s| s.chars().collect()).collect();
+    let chars: Vec<Vec<char>> = char_grid(&f_contents);
 
     _q1(chars).unwrap()
 }
 
 fn _q1(chars: Vec<Vec<char>>) -> Result<usize> {
-    println!("Started Q1 calculation");
+    if debug::verbose() {
+        eprintln!("Started Q1 calculation");
+    }
     let mut maze = PlutoMaze::new(chars)?;
-    println!("{}", maze);
-    println!("Created maze object");
+    if debug::verbose() {
+        eprintln!("{}", maze);
+        eprintln!("Created maze object");
+    }
     maze.find_path_through_maze(false)?;
     Ok(maze.end_distance)
 }
@@ -393,14 +455,16 @@ pub fn q2(fname: String) -> usize {
 
     f.read_to_string(&mut f_contents).expect("Couldn't find file");
 
-    let chars: Vec<Vec<char>> = f_contents.split('\n').filter(|s| s.len() > 0).map(|s| s.chars().collect()).collect();
+    let chars: Vec<Vec<char>> = char_grid(&f_contents);
 
     _q2(chars).unwrap()
 }
 
 fn _q2(chars: Vec<Vec<char>>) -> Result<usize> {
     let mut maze = PlutoMaze::new(chars)?;
-    println!("Created maze object");
+    if debug::verbose() {
+        eprintln!("Created maze object");
+    }
     maze.find_path_through_maze(true)?;
     Ok(maze.end_distance)
 }
@@ -411,7 +475,7 @@ mod tests {
 
     #[test]
     fn day20_q1_test1() {
-        let map: Vec<Vec<char>> = "
+        let map: Vec<Vec<char>> = char_grid("
          A           
          A           
   #######.#########  
@@ -431,7 +495,7 @@ FG..#########.....#
   ###########.#####  
              Z       
              Z       
-        ".split('\n').filter(|line| line.len() > 0).map(|line| line.chars().collect()).collect();
+        ");
 
         assert_eq!(
             _q1(map).unwrap(),
@@ -441,7 +505,7 @@ FG..#########.....#
 
     #[test]
     fn day20_q1_test2() {
-        let map: Vec<Vec<char>> = "
+        let map: Vec<Vec<char>> = char_grid("
                    A               
                    A               
   #################.#############  
@@ -479,7 +543,7 @@ YN......#               VT..#....QG
   #########.###.###.#############  
            B   J   C               
            U   P   P               
-        ".split('\n').filter(|line| line.len() > 0).map(|line| line.chars().collect()).collect();
+        ");
 
         assert_eq!(
             _q1(map).unwrap(),
@@ -487,9 +551,7 @@ YN......#               VT..#....QG
         )
     }
 
-    #[test]
-    fn day20_q2_test() {
-        let map: Vec<Vec<char>> = "
+    const Q2_SAMPLE: &str = "
              Z L X W       C                 
              Z P Q B       K                 
   ###########.#.#.#.#######.###############  
@@ -527,11 +589,99 @@ RE....#.#                           #......RF
   #############.#.#.###.###################  
                A O F   N                     
                A A D   M                     
-        ".split('\n').filter(|&line| line.trim().len() > 0).map(|line| line.chars().collect()).collect();
+        ";
+
+    #[test]
+    fn day20_q2_test() {
+        let map: Vec<Vec<char>> = char_grid(Q2_SAMPLE);
 
         assert_eq!(
             _q2(map).unwrap(),
             396
         )
     }
+
+    #[test]
+    fn day20_max_depth_test() {
+        let mut maze = PlutoMaze::new(char_grid(Q2_SAMPLE)).unwrap();
+
+        maze.find_path_through_maze(false).unwrap();
+        assert_eq!(maze.max_depth_reached(), 0);
+
+        // the 396 step route goes down to level 10 and climbs back out
+        maze.find_path_through_maze(true).unwrap();
+        assert_eq!(maze.end_distance, 396);
+        assert_eq!(maze.max_depth_reached(), 10);
+    }
+
+    #[test]
+    fn day20_solve_test() {
+        let map: Vec<Vec<char>> = char_grid(Q2_SAMPLE);
+
+        let mut maze = PlutoMaze::new(map.clone()).unwrap();
+        assert_eq!(maze.solve().unwrap(), (77, 396));
+        assert_eq!(_q1(map.clone()).unwrap(), 77);
+        assert_eq!(_q2(map).unwrap(), 396);
+    }
+
+    #[test]
+    fn day20_severed_maze_test() {
+        let mut map: Vec<Vec<char>> = char_grid("
+         A           
+         A           
+  #######.#########  
+  #######.........#  
+  #######.#######.#  
+  #######.#######.#  
+  #######.#######.#  
+  #####  B    ###.#  
+BC...##  C    ###.#  
+  ##.##       ###.#  
+  ##...DE  F  ###.#  
+  #####    G  ###.#  
+  #########.#####.#  
+DE..#######...###.#  
+  #.#########.###.#  
+FG..#########.....#  
+  ###########.#####  
+             Z       
+             Z       
+        ");
+
+        // wall off the corridor from AA, cutting it off from everything else
+        map[3][9] = '#';
+
+        let mut maze = PlutoMaze::new(map).unwrap();
+        match maze.find_path_through_maze(false) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "AA at (9, 2) cannot reach ZZ at (13, 16): they're in separate parts of the maze"
+            ),
+            Ok(_) => panic!("There's no way through a severed maze")
+        }
+        assert!(maze.solve().is_err());
+    }
+
+    #[test]
+    fn day20_neighbours_within_test() {
+        let mut corner_neighbours = Coordinate::new(5, 4).neighbours_within(6, 5);
+        corner_neighbours.sort();
+        assert_eq!(
+            corner_neighbours,
+            vec![Coordinate::new(5, 3), Coordinate::new(4, 4)]
+        );
+
+        // the portal's second letter sits in the bottom-right corner of the grid
+        let map: Vec<Vec<char>> = char_grid("
+ A
+ A
+#.###
+#...ZZ
+######
+        ");
+
+        let maze = PlutoMaze::new(map).unwrap();
+        assert_eq!(maze.end_position, Coordinate::new(3, 3));
+        assert!(maze.outside_portals.contains(&Coordinate::new(3, 3)));
+    }
 }
@@ -1,3 +1,20 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::result;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use answer::Answer;
+use intcode;
+
+type Result<T> = result::Result<T, Box<dyn Error>>;
+
 #[allow(dead_code)]
 pub mod day_01;
 
@@ -60,3 +77,461 @@ pub mod day_20;
 
 #[allow(dead_code)]
 pub mod day_21;
+
+// Runs one part of a day on the input file it's given
+pub type Solver = Box<dyn Fn(String) -> Result<Answer>>;
+
+// Stops a hung puzzle instead of letting it spin forever
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    pub timeout: Option<Duration>,
+    // for every Intcode program the solver runs
+    pub max_instructions: Option<usize>
+}
+
+impl Limits {
+    // Reads --timeout <secs> and --max-instructions <n>
+    pub fn from_args(args: &[String]) -> Result<Limits> {
+        let mut limits = Limits::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--timeout" => {
+                    let secs: f64 = args.next().ok_or("--timeout needs a number of seconds")?.parse()?;
+                    limits.timeout = Some(Duration::from_secs_f64(secs));
+                },
+                "--max-instructions" => {
+                    let n: usize = args.next().ok_or("--max-instructions needs a number")?.parse()?;
+                    limits.max_instructions = Some(n);
+                },
+                arg => return Err(Box::<dyn Error>::from(format!("Unknown argument: {}", arg)))
+            }
+        }
+
+        Ok(limits)
+    }
+}
+
+// Takes the day and the part off the front of args, as in `cargo run -- 20 1 --timeout 5`,
+// leaving any options after them for the likes of Limits::from_args
+pub fn day_and_part_from_args(args: &mut Vec<String>) -> Result<(u32, u32)> {
+    if args.len() < 2 || args[..2].iter().any(|arg| arg.starts_with("--")) {
+        return Err(Box::<dyn Error>::from(
+            "Usage: aoc_2019 <day> <part> [--timeout <secs>] [--max-instructions <n>] [--batch <dir>] [--no-cache]"
+        ));
+    }
+
+    let day: u32 = args[0].parse().map_err(|_| format!("Day should be a number, got {}", args[0]))?;
+    let registry = registry();
+    if !registry.contains_key(&day) {
+        let valid_days: Vec<String> = registry.keys().map(|day| day.to_string()).collect();
+        return Err(Box::<dyn Error>::from(format!(
+            "There's no solution for day {}, the valid days are {}", day, valid_days.join(", ")
+        )));
+    }
+
+    let part = match args[1].as_str() {
+        "1" => 1,
+        "2" => 2,
+        part => return Err(Box::<dyn Error>::from(format!("Part should be 1 or 2, got {}", part)))
+    };
+
+    args.drain(..2);
+    Ok((day, part))
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+// Runs a solver on its own thread so that it can be given up on once it's past the timeout.
+// Most days unwrap their errors, so a panic is turned back into an error too.
+pub fn run_with_limits<F>(solve: F, limits: Limits) -> Result<Answer>
+    where F: FnOnce() -> Result<Answer> + Send + 'static
+{
+    let (sender, receiver) = mpsc::channel();
+
+    let max_instructions = limits.max_instructions;
+    let handle = thread::spawn(move || {
+        intcode::set_instruction_limit(max_instructions);
+
+        // errors aren't Send, so pass back the message
+        let _ = sender.send(solve().map_err(|e| e.to_string()));
+    });
+
+    let received = match limits.timeout {
+        Some(timeout) => match receiver.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                return Err(Box::<dyn Error>::from(
+                    format!("Hit the timeout of {} seconds", timeout.as_secs_f64())
+                ));
+            },
+            Err(mpsc::RecvTimeoutError::Disconnected) => None
+        },
+        None => receiver.recv().ok()
+    };
+
+    match received {
+        Some(result) => result.map_err(Box::<dyn Error>::from),
+        None => match handle.join() {
+            Err(payload) => Err(Box::<dyn Error>::from(panic_message(payload))),
+            Ok(()) => Err(Box::<dyn Error>::from("Solver finished without an answer"))
+        }
+    }
+}
+
+// Every file directly inside dir, in name order, for running a day over several inputs
+pub fn input_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).map_err(|e| format!("Cannot read input directory {}: {}", dir.display(), e))? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+// Runs one part of a day on each input in turn, each under the same limits, so that one
+// input failing (or panicking) still leaves the answers for the others
+pub fn run_batch(day: u32, part: u32, files: &[PathBuf], limits: Limits) -> Result<Vec<(PathBuf, Result<Answer>)>> {
+    if !registry().contains_key(&day) {
+        return Err(Box::<dyn Error>::from(format!("No solutions for day {}", day)));
+    }
+    if part != 1 && part != 2 {
+        return Err(Box::<dyn Error>::from(format!("Part must be 1 or 2, not {}", part)));
+    }
+
+    let results = files.iter()
+        .map(|file| {
+            let fname = file.display().to_string();
+            let answer = run_with_limits(move || {
+                let registry = registry();
+                let (ref q1, ref q2) = registry[&day];
+                if part == 1 { q1(fname) } else { q2(fname) }
+            }, limits);
+            (file.clone(), answer)
+        })
+        .collect();
+
+    Ok(results)
+}
+
+// One "file: answer" line per input
+pub fn batch_table(results: &[(PathBuf, Result<Answer>)]) -> String {
+    results.iter()
+        .map(|(file, answer)| match answer {
+            Ok(answer) => format!("{}: {}\n", file.display(), answer),
+            Err(e) => format!("{}: Error: {}\n", file.display(), e)
+        })
+        .collect()
+}
+
+// What main prints once a day has finished, as (stdout, stderr). The answer is the only
+// thing on stdout, so it can be piped somewhere; the days' own output all goes to stderr too.
+pub fn report(result: &Result<Answer>, elapsed: Duration) -> (String, String) {
+    let timing = format!("Elapsed time: {:?}\n", elapsed);
+    match result {
+        Ok(answer) => (format!("Answer: {}\n", answer), timing),
+        Err(e) => (String::new(), format!("Error: {}\n{}", e, timing))
+    }
+}
+
+// Where each day's puzzle input lives, e.g. ./inputs/day07.txt
+pub fn input_path(day: u32) -> PathBuf {
+    PathBuf::from(format!("./inputs/day{:02}.txt", day))
+}
+
+pub fn load_day_input(day: u32) -> Result<String> {
+    let path = input_path(day);
+    let mut f = File::open(&path)
+        .map_err(|e| format!("Cannot open input for day {} at {}: {}", day, path.display(), e))?;
+    let mut f_contents = String::new();
+
+    f.read_to_string(&mut f_contents)?;
+
+    Ok(f_contents)
+}
+
+// day_04's input is a range like 123456-654321 rather than a list
+fn read_range(fname: String) -> Result<(u32, u32)> {
+    let mut f = File::open(fname)?;
+    let mut f_contents = String::new();
+
+    f.read_to_string(&mut f_contents)?;
+
+    let mut bounds = f_contents.trim().split('-');
+    match (bounds.next(), bounds.next(), bounds.next()) {
+        (Some(start), Some(finish), None) => Ok((start.parse()?, finish.parse()?)),
+        _ => Err(Box::<dyn Error>::from(format!("Cannot read range: {}", f_contents.trim())))
+    }
+}
+
+// The lines of a map read from a file or written out in a test, without any blank lines
+// before or after it. Everything else, including leading spaces, is left alone.
+pub fn map_lines(map: &str) -> Vec<&str> {
+    let lines: Vec<&str> = map.lines().collect();
+
+    let start = lines.iter().position(|line| !line.trim().is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|line| !line.trim().is_empty()).map_or(start, |idx| idx + 1);
+
+    lines[start..end].to_vec()
+}
+
+pub fn char_grid(map: &str) -> Vec<Vec<char>> {
+    map_lines(map).into_iter().map(|line| line.chars().collect()).collect()
+}
+
+// (q1, q2) for every day, keyed by day number
+pub fn registry() -> BTreeMap<u32, (Solver, Solver)> {
+    let mut registry = BTreeMap::new();
+
+    registry.insert(1, (
+        Box::new(|fname| Ok(day_01::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_01::q2(fname).into())) as Solver
+    ));
+    registry.insert(2, (
+        Box::new(|fname| Ok(day_02::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_02::q2(fname).into())) as Solver
+    ));
+    registry.insert(3, (
+        Box::new(|fname| Ok(day_03::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_03::q2(fname).into())) as Solver
+    ));
+    registry.insert(4, (
+        Box::new(|fname| {
+            let (start, finish) = read_range(fname)?;
+            Ok(day_04::q1(start, finish).into())
+        }) as Solver,
+        Box::new(|fname| {
+            let (start, finish) = read_range(fname)?;
+            Ok(day_04::q2(start, finish).into())
+        }) as Solver
+    ));
+    registry.insert(5, (
+        Box::new(|fname| Ok(day_05::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_05::q2(fname).into())) as Solver
+    ));
+    registry.insert(6, (
+        Box::new(|fname| Ok(day_06::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_06::q2(fname).into())) as Solver
+    ));
+    registry.insert(7, (
+        Box::new(|fname| Ok(day_07::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_07::q2(fname).into())) as Solver
+    ));
+    registry.insert(8, (
+        Box::new(|fname| Ok(day_08::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_08::q2(fname).into())) as Solver
+    ));
+    registry.insert(9, (
+        Box::new(|fname| Ok(day_09::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_09::q2(fname).into())) as Solver
+    ));
+    registry.insert(10, (
+        Box::new(|fname| Ok(day_10::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_10::q2(fname).into())) as Solver
+    ));
+    registry.insert(11, (
+        Box::new(|fname| Ok(day_11::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_11::q2(fname).into())) as Solver
+    ));
+    registry.insert(12, (
+        Box::new(|fname| Ok(day_12::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_12::q2(fname).into())) as Solver
+    ));
+    registry.insert(13, (
+        Box::new(|fname| Ok(day_13::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_13::q2(fname).into())) as Solver
+    ));
+    registry.insert(14, (
+        Box::new(|fname| Ok(day_14::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_14::q2(fname).into())) as Solver
+    ));
+    registry.insert(15, (
+        Box::new(|fname| Ok(day_15::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_15::q2(fname).into())) as Solver
+    ));
+    registry.insert(16, (
+        Box::new(|fname| Ok(day_16::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_16::q2(fname).into())) as Solver
+    ));
+    registry.insert(17, (
+        Box::new(|fname| Ok(day_17::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_17::q2(fname).into())) as Solver
+    ));
+    registry.insert(18, (
+        Box::new(|fname| Ok(day_18::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_18::q2(fname).into())) as Solver
+    ));
+    registry.insert(19, (
+        Box::new(|fname| Ok(day_19::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_19::q2(fname).into())) as Solver
+    ));
+    registry.insert(20, (
+        Box::new(|fname| Ok(day_20::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_20::q2(fname).into())) as Solver
+    ));
+    registry.insert(21, (
+        Box::new(|fname| Ok(day_21::q1(fname).into())) as Solver,
+        Box::new(|fname| Ok(day_21::q2(fname).into())) as Solver
+    ));
+
+    registry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_test() {
+        let registry = registry();
+
+        assert_eq!(
+            registry.keys().cloned().collect::<Vec<u32>>(),
+            (1..=21).collect::<Vec<u32>>()
+        );
+    }
+
+    #[test]
+    fn day_and_part_from_args_test() {
+        let to_args = |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+
+        let mut args = to_args(&["20", "1", "--timeout", "5"]);
+        assert_eq!(day_and_part_from_args(&mut args).unwrap(), (20, 1));
+        assert_eq!(args, to_args(&["--timeout", "5"]));
+
+        let mut args = to_args(&["30", "1"]);
+        assert_eq!(
+            day_and_part_from_args(&mut args).unwrap_err().to_string(),
+            "There's no solution for day 30, the valid days are 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21"
+        );
+        assert_eq!(args.len(), 2);
+
+        assert!(day_and_part_from_args(&mut to_args(&["7", "3"])).is_err());
+        assert!(day_and_part_from_args(&mut to_args(&["seven", "1"])).is_err());
+        assert!(day_and_part_from_args(&mut to_args(&["7"])).is_err());
+        assert!(day_and_part_from_args(&mut to_args(&["7", "--no-cache"])).is_err());
+        assert!(day_and_part_from_args(&mut vec![]).unwrap_err().to_string().starts_with("Usage:"));
+    }
+
+    #[test]
+    fn limits_from_args_test() {
+        let args: Vec<String> = vec!["--max-instructions", "500", "--timeout", "2.5"]
+            .into_iter().map(|s| s.to_string()).collect();
+
+        assert_eq!(
+            Limits::from_args(&args).unwrap(),
+            Limits { timeout: Some(Duration::from_millis(2500)), max_instructions: Some(500) }
+        );
+        assert_eq!(Limits::from_args(&[]).unwrap(), Limits::default());
+        assert!(Limits::from_args(&["--timeout".to_string()]).is_err());
+        assert!(Limits::from_args(&["--verbose".to_string()]).is_err());
+    }
+
+    #[test]
+    fn run_with_limits_test() {
+        let instruction_limit = Limits { timeout: None, max_instructions: Some(1000) };
+        let result = run_with_limits(|| {
+            let mut program = intcode::Program::new(vec![1105, 1, 0]);
+            let outputs = program.run_to_completion()?;
+            Ok(Answer::from(outputs.len()))
+        }, instruction_limit);
+        match result {
+            Err(e) => assert_eq!(e.to_string(), "Hit the instruction limit of 1000 instructions"),
+            Ok(_) => panic!("An infinite loop shouldn't complete")
+        }
+
+        // the same thing unwrapped inside the solver, like most of the days do
+        let result = run_with_limits(|| {
+            let mut program = intcode::Program::new(vec![1105, 1, 0]);
+            Ok(Answer::from(program.run_to_completion().unwrap().len()))
+        }, instruction_limit);
+        assert!(result.unwrap_err().to_string().contains("Hit the instruction limit of 1000 instructions"));
+
+        let timeout = Limits { timeout: Some(Duration::from_millis(50)), max_instructions: None };
+        let result = run_with_limits(|| {
+            thread::sleep(Duration::from_secs(5));
+            Ok(Answer::from(0usize))
+        }, timeout);
+        assert_eq!(result.unwrap_err().to_string(), "Hit the timeout of 0.05 seconds");
+
+        assert_eq!(
+            run_with_limits(|| Ok(Answer::from(42usize)), timeout).unwrap().to_string(),
+            "42"
+        );
+    }
+
+    #[test]
+    fn run_batch_test() {
+        let dir = std::env::temp_dir().join(format!("aoc_2019_batch_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "12\n14\n").unwrap();
+        fs::write(dir.join("b.txt"), "1969\n").unwrap();
+
+        let files = input_files(&dir).unwrap();
+        assert_eq!(files, vec![dir.join("a.txt"), dir.join("b.txt")]);
+
+        let results = run_batch(1, 1, &files, Limits::default()).unwrap();
+        let answers: Vec<String> = results.iter()
+            .map(|(_, answer)| answer.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(answers, vec!["4", "654"]);
+        assert_eq!(
+            batch_table(&results),
+            format!("{}: 4\n{}: 654\n", dir.join("a.txt").display(), dir.join("b.txt").display())
+        );
+
+        assert!(run_batch(99, 1, &files, Limits::default()).is_err());
+        assert!(run_batch(1, 3, &files, Limits::default()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn report_test() {
+        let (stdout, stderr) = report(&Ok(Answer::from(42usize)), Duration::from_millis(3));
+        assert_eq!(stdout, "Answer: 42\n");
+        assert_eq!(stderr, "Elapsed time: 3ms\n");
+
+        let failed: Result<Answer> = Err(Box::<dyn Error>::from("Hit the timeout of 1 seconds"));
+        let (stdout, stderr) = report(&failed, Duration::from_secs(1));
+        assert_eq!(stdout, "");
+        assert_eq!(stderr, "Error: Hit the timeout of 1 seconds\nElapsed time: 1s\n");
+    }
+
+    #[test]
+    fn input_path_test() {
+        assert_eq!(input_path(7), PathBuf::from("./inputs/day07.txt"));
+        assert_eq!(input_path(21), PathBuf::from("./inputs/day21.txt"));
+
+        match load_day_input(99) {
+            Err(e) => assert!(e.to_string().starts_with("Cannot open input for day 99 at ./inputs/day99.txt")),
+            Ok(_) => panic!("There's no input for day 99")
+        }
+    }
+
+    #[test]
+    fn char_grid_test() {
+        let trimmed = "  #.\n\n ## #";
+        let padded = "\n   \n  #.\n\n ## #\n\t\n";
+
+        assert_eq!(char_grid(padded), char_grid(trimmed));
+        assert_eq!(
+            char_grid(trimmed),
+            vec![vec![' ', ' ', '#', '.'], vec![], vec![' ', '#', '#', ' ', '#']]
+        );
+        assert!(char_grid(" \n\n").is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use std::cell::Cell;
+use std::io;
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+thread_local! {
+    // Whether pause actually waits for a key on this thread
+    static PAUSING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Whether the days print their working out as they go. Unlike PAUSING this is shared between
+// threads, as the solve runs on a thread of its own.
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+// Turned on by --verbose
+pub fn set_verbose(enabled: bool) {
+    VERBOSE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+// pause does nothing until this is turned on, so it's safe to leave calls to it in a solve
+pub fn set_pausing(enabled: bool) {
+    PAUSING.with(|pausing| pausing.set(enabled));
+}
+
+// For stepping through a solve by hand: waits for a key, if set_pausing is on
+pub fn pause() {
+    if !PAUSING.with(|pausing| pausing.get()) {
+        return;
+    }
+
+    // The prompt goes to stderr like the rest of the diagnostics, without a newline so the
+    // cursor stays at the end of it
+    eprint!("Press any key to continue...");
+    io::stderr().flush().unwrap();
+
+    // Read a single byte and discard
+    let _ = io::stdin().read(&mut [0u8]).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_pause_off_test() {
+        // pausing starts off, so this would hang waiting on stdin if the gate didn't work
+        pause();
+
+        set_pausing(true);
+        set_pausing(false);
+        pause();
+    }
+}
@@ -0,0 +1,147 @@
+use std::error::Error;
+use std::result;
+
+type Result<T> = result::Result<T, Box<dyn Error>>;
+
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
+}
+
+// Rectangular grid of tiles stored row by row in one Vec, for the maps that are walked over
+// a lot. Looking a square up is just index arithmetic rather than a search through a BTreeMap.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DenseGrid<T> {
+    tiles: Vec<T>,
+    width: usize,
+    height: usize
+}
+
+impl<T: Clone> DenseGrid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> DenseGrid<T> {
+        DenseGrid {
+            tiles: vec![fill; width * height],
+            width,
+            height
+        }
+    }
+}
+
+impl<T> DenseGrid<T> {
+    // Every row has to be the same length
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<DenseGrid<T>> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+
+        let mut tiles = Vec::with_capacity(width * height);
+        for (y, row) in rows.into_iter().enumerate() {
+            if row.len() != width {
+                return err!("Row {} has {} tiles, expected {}", y, row.len(), width);
+            }
+            tiles.extend(row);
+        }
+
+        Ok(DenseGrid { tiles, width, height })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Where (x, y) is in tiles, or None if it's off the grid
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    // The (x, y) that's stored at idx in tiles
+    pub fn coordinate(&self, idx: usize) -> Option<(usize, usize)> {
+        if idx < self.tiles.len() {
+            Some((idx % self.width, idx / self.width))
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.index(x, y).map(|idx| &self.tiles[idx])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, tile: T) -> Result<()> {
+        match self.index(x, y) {
+            Some(idx) => {
+                self.tiles[idx] = tile;
+                Ok(())
+            },
+            None => err!("({}, {}) is outside a {}x{} grid", x, y, self.width, self.height)
+        }
+    }
+
+    // The squares next to (x, y) that are on the grid: below, right, left then above
+    pub fn neighbours(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut result = vec![];
+        if y + 1 < self.height {
+            result.push((x, y + 1));
+        }
+        if x + 1 < self.width {
+            result.push((x + 1, y));
+        }
+        if x > 0 {
+            result.push((x - 1, y));
+        }
+        if y > 0 {
+            result.push((x, y - 1));
+        }
+
+        result
+    }
+
+    // All the tiles with their coordinates, in row order
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let width = self.width;
+        self.tiles.iter().enumerate().map(move |(idx, tile)| ((idx % width, idx / width), tile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dense_grid_index_test() {
+        let mut grid = DenseGrid::new(3, 2, '.');
+        grid.set(2, 1, '#').unwrap();
+
+        assert_eq!(grid.index(2, 1), Some(5));
+        assert_eq!(grid.coordinate(5), Some((2, 1)));
+        assert_eq!(grid.index(3, 0), None);
+        assert_eq!(grid.coordinate(6), None);
+        assert_eq!(grid.get(2, 1), Some(&'#'));
+        assert_eq!(grid.get(0, 2), None);
+        assert!(grid.set(0, 2, '#').is_err());
+
+        for idx in 0..6 {
+            let (x, y) = grid.coordinate(idx).unwrap();
+            assert_eq!(grid.index(x, y), Some(idx));
+        }
+    }
+
+    #[test]
+    fn dense_grid_from_rows_test() {
+        let grid = DenseGrid::from_rows(vec![vec!['#', '#', '#'], vec!['#', '@', '#']]).unwrap();
+
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get(1, 1), Some(&'@'));
+        assert_eq!(grid.neighbours(0, 0), vec![(0, 1), (1, 0)]);
+        assert_eq!(grid.neighbours(1, 1), vec![(2, 1), (0, 1), (1, 0)]);
+        assert_eq!(grid.iter().nth(4), Some(((1, 1), &'@')));
+
+        assert!(DenseGrid::from_rows(vec![vec!['#', '#'], vec!['#']]).is_err());
+    }
+}
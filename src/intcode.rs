@@ -0,0 +1,1072 @@
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use std::result;
+
+use std::collections::VecDeque;
+
+type Result<T> = result::Result<T, Box<dyn Error>>;
+
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn Error>::from(format!($($tt)*))) }
+}
+
+#[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
+enum Parameter {
+    Position,
+    Immediate,
+    Relative
+}
+
+#[derive(Clone, Eq, Default, Debug, PartialEq, Hash)]
+struct Instruction {
+    opcode: usize,
+    parameters: Vec<Parameter>
+}
+
+impl Instruction {
+    fn new(number: usize) -> Result<Instruction> {
+        let opcode = number % 100;
+        let mut digit_list: Vec<_> = (number / 100).to_string().chars().map(|d| d.to_digit(10).unwrap()).collect();
+        digit_list.reverse();
+
+        let params_length = match opcode {
+            1 => 3,
+            2 => 3,
+            3 => 1,
+            4 => 1,
+            5 => 2,
+            6 => 2,
+            7 => 3,
+            8 => 3,
+            9 => 1,
+            99 => 0,
+            x => return err!("{}", format!("Cannot read opcode: {}", x))
+        };
+
+        digit_list.resize(params_length, 0);
+        let parameters: Result<Vec<Parameter>> = digit_list.into_iter().map(|d| match d {
+            0 => Ok(Parameter::Position),
+            1 => Ok(Parameter::Immediate),
+            2 => Ok(Parameter::Relative),
+            x => err!("{}", format!("Cannot read parameter digit: {}", x))
+        }).collect();
+        let parameters = parameters?;
+
+        Ok(
+            Instruction {
+                opcode,
+                parameters,
+            }
+        )
+
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        match self.opcode {
+            1 => "add",
+            2 => "mul",
+            3 => "in",
+            4 => "out",
+            5 => "jnz",
+            6 => "jz",
+            7 => "lt",
+            8 => "eq",
+            9 => "arb",
+            _ => "halt"
+        }
+    }
+}
+
+// How an operand is shown in a disassembly: [n] for an address, [rb+n] relative to the
+// relative base, and just n for an immediate value
+fn format_operand(parameter: Parameter, val: i64) -> String {
+    match parameter {
+        Parameter::Position => format!("[{}]", val),
+        Parameter::Immediate => format!("{}", val),
+        Parameter::Relative => format!("[rb{:+}]", val)
+    }
+}
+
+// Somewhere for a Program to get its inputs from once its queued inputs have run out,
+// e.g. something reacting to what the program has output so far
+pub trait InputSource {
+    fn next_input(&mut self) -> Result<i64>;
+
+    // needed so that a Program holding an input source can still be forked
+    fn box_clone(&self) -> Box<dyn InputSource>;
+}
+
+impl Clone for Box<dyn InputSource> {
+    fn clone(&self) -> Box<dyn InputSource> {
+        self.box_clone()
+    }
+}
+
+impl fmt::Debug for dyn InputSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InputSource")
+    }
+}
+
+// A fixed script of inputs
+impl InputSource for VecDeque<i64> {
+    fn next_input(&mut self) -> Result<i64> {
+        let input = self.pop_front().ok_or("No inputs left!")?;
+
+        Ok(input)
+    }
+
+    fn box_clone(&self) -> Box<dyn InputSource> {
+        Box::new(self.clone())
+    }
+}
+
+// Prompts the ASCII programs print while waiting for their input
+const KNOWN_PROMPTS: [&str; 6] = [
+    "Main:",
+    "Function A:",
+    "Function B:",
+    "Function C:",
+    "Continuous video feed?",
+    "Input instructions:",
+];
+
+// An ASCII transcript with any lines that are just a known prompt taken out
+pub fn without_prompts(transcript: &str) -> String {
+    transcript
+        .lines()
+        .filter(|line| !KNOWN_PROMPTS.contains(&line.trim()))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+thread_local! {
+    // Instruction budget given to every Program created on this thread
+    static INSTRUCTION_LIMIT: Cell<Option<usize>> = const { Cell::new(None) };
+    // Whether Programs created on this thread count how often each opcode runs
+    static PROFILING: Cell<bool> = const { Cell::new(false) };
+    // Whether Programs created on this thread keep a log of their inputs and outputs
+    static IO_RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Programs created on this thread after this will error once they've run this many instructions
+pub fn set_instruction_limit(limit: Option<usize>) {
+    INSTRUCTION_LIMIT.with(|instruction_limit| instruction_limit.set(limit));
+}
+
+// Programs created on this thread after this will keep an opcode_histogram
+pub fn set_profiling(enabled: bool) {
+    PROFILING.with(|profiling| profiling.set(enabled));
+}
+
+// Programs created on this thread after this will keep an io_transcript
+pub fn set_io_recording(enabled: bool) {
+    IO_RECORDING.with(|io_recording| io_recording.set(enabled));
+}
+
+// Where an opcode is counted in opcode_histogram: 1-9 in their own slot, 99 in slot 0
+fn histogram_slot(opcode: usize) -> usize {
+    if opcode == 99 { 0 } else { opcode }
+}
+
+// An input read or an output written, with the instruction count it happened at
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum IoEvent {
+    Input(usize, i64),
+    Output(usize, i64)
+}
+
+impl fmt::Display for IoEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IoEvent::Input(step, val) => write!(f, "step {}: input {}", step, val),
+            IoEvent::Output(step, val) => write!(f, "step {}: output {}", step, val)
+        }
+    }
+}
+
+// What happened after executing a single instruction
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HaltReason {
+    // an ordinary instruction ran, carry on
+    Stepped,
+    Output(i64),
+    // the next instruction reads an input but there isn't one; nothing was executed
+    NeedsInput,
+    // the program has reached a 99
+    Halted
+}
+
+// What step_until_event stopped for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StepResult {
+    Output(i64),
+    // the next instruction reads an input and there isn't one; give it one with add_input
+    NeedInput,
+    Halted
+}
+
+// Another name for Program, for callers that think of it as the machine rather than the code
+pub type Computer = Program;
+
+#[derive(Clone, Debug)]
+pub struct Program {
+    // the program as it was loaded, to compare the running memory against.
+    // Shared between every machine built from the same image and all their forks.
+    initial_memory: Rc<[i64]>,
+    memory: Vec<i64>,
+    inputs: VecDeque<i64>,
+    input_source: Option<Box<dyn InputSource>>,
+    pointer_idx: usize,
+    relative_base: i64,
+    instructions_run: usize,
+    instruction_limit: Option<usize>,
+    outputs_produced: usize,
+    // only kept when profiling was turned on
+    opcode_counts: Option<[usize; 10]>,
+    // only kept when io recording was turned on
+    io_log: Option<Vec<IoEvent>>,
+}
+
+impl Program {
+    pub fn new(memory: Vec<i64>)  -> Program {
+        Program::from_image(Rc::from(memory))
+    }
+
+    // Leaves memory alone, so it can be used to build as many machines as needed
+    pub fn from_slice(memory: &[i64]) -> Program {
+        Program::from_image(Rc::from(memory))
+    }
+
+    // Only the running memory is copied out of the image; the image itself is shared
+    pub fn from_image(image: Rc<[i64]>) -> Program {
+        Program {
+            memory: image.to_vec(),
+            initial_memory: image,
+            inputs: VecDeque::new(),
+            input_source: None,
+            pointer_idx: 0,
+            relative_base: 0,
+            instructions_run: 0,
+            instruction_limit: INSTRUCTION_LIMIT.with(|instruction_limit| instruction_limit.get()),
+            outputs_produced: 0,
+            opcode_counts: if PROFILING.with(|profiling| profiling.get()) {
+                Some([0; 10])
+            } else {
+                None
+            },
+            io_log: if IO_RECORDING.with(|io_recording| io_recording.get()) {
+                Some(Vec::new())
+            } else {
+                None
+            },
+        }
+    }
+
+    // Used for any inputs needed after the ones queued up with add_input
+    pub fn set_input_source<S: InputSource + 'static>(&mut self, input_source: S) {
+        self.input_source = Some(Box::new(input_source));
+    }
+
+    // An independent copy of this machine: memory, pointer, relative base and any queued
+    // inputs are all copied, so running one copy never affects the other
+    pub fn fork(&self) -> Program {
+        self.clone()
+    }
+
+    // How many outputs the program has produced so far
+    pub fn output_count(&self) -> usize {
+        self.outputs_produced
+    }
+
+    // How many times each opcode has run, with 99 in slot 0. All zeroes unless the
+    // program was created with profiling on; see set_profiling.
+    pub fn opcode_histogram(&self) -> [usize; 10] {
+        self.opcode_counts.unwrap_or([0; 10])
+    }
+
+    // Every input read and output written so far, one per line in the order they happened,
+    // for pasting into a bug report. Empty unless the program was created with io recording
+    // on; see set_io_recording.
+    pub fn io_transcript(&self) -> String {
+        self.io_log.iter()
+            .flatten()
+            .map(|event| format!("{}\n", event))
+            .collect()
+    }
+
+    // One line per instruction of the program as it was loaded, e.g. "4: out [0]", starting
+    // from address 0 and stopping at the first value that isn't an instruction (or doesn't
+    // have all of its operands). Data after a halt is shown as instructions if it decodes.
+    pub fn disassemble(&self) -> Vec<String> {
+        let mut listing = vec![];
+
+        let mut idx = 0;
+        while idx < self.initial_memory.len() {
+            let val = self.initial_memory[idx];
+            let instruction = match Instruction::new(val as usize) {
+                Ok(instruction) if val >= 0 => instruction,
+                _ => break
+            };
+
+            let operand_count = instruction.parameters.len();
+            if idx + operand_count >= self.initial_memory.len() {
+                break;
+            }
+
+            let operands: Vec<String> = instruction.parameters.iter()
+                .zip(self.initial_memory[idx+1..=idx+operand_count].iter())
+                .map(|(&parameter, &val)| format_operand(parameter, val))
+                .collect();
+
+            if operands.is_empty() {
+                listing.push(format!("{}: {}", idx, instruction.mnemonic()));
+            } else {
+                listing.push(format!("{}: {} {}", idx, instruction.mnemonic(), operands.join(", ")));
+            }
+
+            idx += 1 + operand_count;
+        }
+
+        listing
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.memory
+    }
+
+    // For patching the program before it runs, like the noun and verb in day 2
+    pub fn memory_mut(&mut self) -> &mut Vec<i64> {
+        &mut self.memory
+    }
+
+    // Every address whose value isn't what it was when the program was loaded.
+    // Memory the program has grown into only counts if something non-zero was written there.
+    pub fn modified_addresses(&self) -> Vec<usize> {
+        self.memory.iter()
+            .enumerate()
+            .filter(|&(idx, &val)| val != self.initial_memory.get(idx).cloned().unwrap_or(0))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    // Reads any address, growing memory with zeroes like the program itself would
+    pub fn read_mem(&mut self, idx: usize) -> i64 {
+        if self.memory.len() < idx+1 {
+            self.memory.resize(idx+1, 0);
+        }
+
+        self.memory[idx]
+    }
+
+    fn get_input(&mut self) -> Result<i64> {
+        match (self.inputs.pop_front(), &mut self.input_source) {
+            (Some(input), _) => Ok(input),
+            (None, Some(input_source)) => input_source.next_input(),
+            (None, None) => err!("No inputs left!")
+        }
+    }
+
+    pub fn add_input(&mut self, input: i64) {
+        self.inputs.push_back(input);
+    }
+
+    // Casting a negative address straight to usize would wrap round to a huge one
+    fn address(&self, val: i64) -> Result<usize> {
+        if val < 0 {
+            return err!("negative memory address {}", val);
+        }
+
+        Ok(val as usize)
+    }
+
+    fn get_parameter(&mut self, parameter_form: Parameter, val: i64) -> Result<i64> {
+        use self::Parameter::*;
+
+        match parameter_form {
+            Position => {
+                let idx = self.address(val)?;
+                if self.memory.len() < idx+1 {
+                    self.memory.resize(idx+1, 0);
+                }
+
+                Ok(self.memory[idx])
+            },
+            Immediate => Ok(val),
+            Relative => {
+                let idx = self.address(self.relative_base + val)?;
+                if self.memory.len() < idx+1 {
+                    self.memory.resize(idx+1, 0);
+                }
+
+                Ok(self.memory[idx])
+            }
+        }
+    }
+
+    // The operand offset places after the opcode. A truncated last instruction reads zeroes
+    // rather than running off the end of memory.
+    fn operand(&mut self, parameter_form: Parameter, offset: usize) -> Result<i64> {
+        let val = self.read_mem(self.pointer_idx + offset);
+        self.get_parameter(parameter_form, val)
+    }
+
+    fn set_parameter(&mut self, idx: usize, val: i64) -> Result<()> {
+        if self.memory.len() < idx+1 {
+            self.memory.resize(idx+1, 0);
+        }
+
+        self.memory[idx] = val;
+
+        Ok(())
+    }
+
+    // An instruction's pointer is still on it while its output address is worked out, so the
+    // opcode for the error comes from there
+    fn get_output_idx(&mut self, idx: usize, parameter_type: Parameter) -> Result<usize> {
+        use self::Parameter::*;
+        if self.memory.len() < idx+1 {
+            self.memory.resize(idx+1, 0);
+        }
+        match parameter_type {
+            Position => {
+                self.address(self.memory[idx])
+            },
+            Relative => {
+                self.address(self.memory[idx] + self.relative_base)
+            },
+            Immediate => err!("opcode {} cannot use immediate mode", self.memory[self.pointer_idx] % 100)
+        }
+    }
+
+    // Runs until the program halts, collecting every output in order
+    pub fn run_to_completion(&mut self) -> Result<Vec<i64>> {
+        let mut outputs = Vec::new();
+        while let Some(output) = self.run_until_output()? {
+            outputs.push(output);
+        }
+
+        Ok(outputs)
+    }
+
+    // Runs until an output that pred accepts and returns it, skipping over any others.
+    // Returns None if the program halts first.
+    pub fn run_until<F: Fn(i64) -> bool>(&mut self, pred: F) -> Result<Option<i64>> {
+        while let Some(output) = self.run_until_output()? {
+            if pred(output) {
+                return Ok(Some(output));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Feeds each line in as ASCII followed by a newline, then runs until the program halts.
+    // Returns everything printed as text, plus the last output too large to be a character.
+    pub fn run_ascii_program(&mut self, lines: &[&str]) -> Result<(String, Option<i64>)> {
+        for line in lines {
+            for c in line.chars() {
+                self.add_input(c as i64);
+            }
+            self.add_input('\n' as i64);
+        }
+
+        let mut text = String::new();
+        let mut large_value = None;
+        while let Some(output) = self.run_until_output()? {
+            if (0..=255).contains(&output) {
+                text.push(output as u8 as char);
+            } else {
+                large_value = Some(output);
+            }
+        }
+
+        Ok((text, large_value))
+    }
+
+    // Runs until the next output instruction and returns its value, leaving the program paused
+    // just after it so the next call carries on from there. Returns None once the program halts.
+    pub fn run_until_output(&mut self) -> Result<Option<i64>> {
+        match self.step_until_event()? {
+            StepResult::Output(output_val) => Ok(Some(output_val)),
+            StepResult::NeedInput => err!("No inputs left!"),
+            StepResult::Halted => Ok(None)
+        }
+    }
+
+    // Runs until there's an output, the program wants an input or it halts. Running out of
+    // inputs isn't an error here, so a caller can work each input out only once it's asked for.
+    pub fn step_until_event(&mut self) -> Result<StepResult> {
+        match self.run_until_blocked()? {
+            HaltReason::Output(output_val) => Ok(StepResult::Output(output_val)),
+            HaltReason::NeedsInput => Ok(StepResult::NeedInput),
+            HaltReason::Halted => Ok(StepResult::Halted),
+            HaltReason::Stepped => unreachable!("run_until_blocked keeps going while it can step")
+        }
+    }
+
+    // Runs until the program can't carry on by itself: it has output something, it's waiting
+    // for an input, or it has halted. Waiting doesn't run anything, so a scheduler can keep
+    // coming back to a blocked machine (after an add_input, say) without it costing anything.
+    pub fn run_until_blocked(&mut self) -> Result<HaltReason> {
+        loop {
+            match self.step()? {
+                HaltReason::Stepped => continue,
+                halt_reason => return Ok(halt_reason)
+            }
+        }
+    }
+
+    // Executes exactly one instruction. Halting leaves the pointer on the 99, so stepping
+    // again just halts again. Waiting for an input doesn't count as running an instruction.
+    pub fn step(&mut self) -> Result<HaltReason> {
+        if self.pointer_idx >= self.memory.len() {
+            return err!("instruction pointer {} is past the end of memory", self.pointer_idx);
+        }
+        let current_instruction = Instruction::new(self.memory[self.pointer_idx] as usize)?;
+
+        if current_instruction.opcode == 3 && self.inputs.is_empty() && self.input_source.is_none() {
+            return Ok(HaltReason::NeedsInput);
+        }
+
+        if let Some(limit) = self.instruction_limit {
+            if self.instructions_run >= limit {
+                return err!("Hit the instruction limit of {} instructions", limit);
+            }
+        }
+        self.instructions_run += 1;
+        if let Some(opcode_counts) = self.opcode_counts.as_mut() {
+            if current_instruction.opcode == 99 || current_instruction.opcode <= 9 {
+                opcode_counts[histogram_slot(current_instruction.opcode)] += 1;
+            }
+        }
+
+        match current_instruction.opcode {
+            1 => {
+                let input_1 = self.operand(current_instruction.parameters[0], 1)?;
+                let input_2 = self.operand(current_instruction.parameters[1], 2)?;
+                let output_idx = self.get_output_idx(
+                    self.pointer_idx + 3,
+                    current_instruction.parameters[2]
+                )?;
+                self.set_parameter(output_idx, input_1 + input_2)?;
+
+                self.pointer_idx += 4;
+            },
+            2 => {
+                let input_1 = self.operand(current_instruction.parameters[0], 1)?;
+                let input_2 = self.operand(current_instruction.parameters[1], 2)?;
+                let output_idx = self.get_output_idx(
+                    self.pointer_idx + 3,
+                    current_instruction.parameters[2]
+                )?;
+                self.set_parameter(output_idx, input_1 * input_2)?;
+
+                self.pointer_idx += 4;
+            },
+            3 => {
+                let output_idx = self.get_output_idx(
+                    self.pointer_idx + 1,
+                    current_instruction.parameters[0]
+                )?;
+                let input = self.get_input()?;
+                self.set_parameter(output_idx, input)?;
+                if let Some(io_log) = self.io_log.as_mut() {
+                    io_log.push(IoEvent::Input(self.instructions_run, input));
+                }
+
+                self.pointer_idx += 2;
+            },
+            4 => {
+                let output_val = self.operand(current_instruction.parameters[0], 1)?;
+
+                // let output_idx = self.memory[self.pointer_idx+1];
+                self.pointer_idx += 2;
+                self.outputs_produced += 1;
+                if let Some(io_log) = self.io_log.as_mut() {
+                    io_log.push(IoEvent::Output(self.instructions_run, output_val));
+                }
+
+                return Ok(HaltReason::Output(output_val));
+            },
+            5 => {
+                let input_1 = self.operand(current_instruction.parameters[0], 1)?;
+                let input_2 = self.operand(current_instruction.parameters[1], 2)?;
+                if input_1 != 0 {
+                    self.pointer_idx = self.address(input_2)?;
+                } else {
+                    self.pointer_idx += 3;
+                }
+            },
+            6 => {
+                let input_1 = self.operand(current_instruction.parameters[0], 1)?;
+                let input_2 = self.operand(current_instruction.parameters[1], 2)?;
+                if input_1 == 0 {
+                    self.pointer_idx = self.address(input_2)?;
+                } else {
+                    self.pointer_idx += 3;
+                }
+            },
+            7 => {
+                let input_1 = self.operand(current_instruction.parameters[0], 1)?;
+                let input_2 = self.operand(current_instruction.parameters[1], 2)?;
+                let output_idx = self.get_output_idx(
+                    self.pointer_idx + 3,
+                    current_instruction.parameters[2]
+                )?;
+                self.set_parameter(output_idx, if input_1 < input_2 {1} else {0})?;
+
+                self.pointer_idx += 4;
+            },
+            8 => {
+                let input_1 = self.operand(current_instruction.parameters[0], 1)?;
+                let input_2 = self.operand(current_instruction.parameters[1], 2)?;
+                let output_idx = self.get_output_idx(
+                    self.pointer_idx + 3,
+                    current_instruction.parameters[2]
+                )?;
+                self.set_parameter(output_idx, if input_1 == input_2 {1} else {0})?;
+
+                self.pointer_idx += 4;
+            },
+            9 => {
+                let input_1 = self.operand(current_instruction.parameters[0], 1)?;
+                self.relative_base += input_1;
+
+                self.pointer_idx += 2;
+            },
+            99 => return Ok(HaltReason::Halted),
+            x => return err!("{}", format!("Incorrect opcode: {}", x))
+        }
+
+        Ok(HaltReason::Stepped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intcode_fork_test() {
+        let mut program = Program::new(vec![3, 0, 1, 0, 0, 0, 99]);
+        program.add_input(21);
+
+        let mut forked_program = program.fork();
+        assert_eq!(forked_program.run_until_output().unwrap(), None);
+        assert_eq!(forked_program.memory[0], 42);
+        assert!(forked_program.inputs.is_empty());
+
+        // the original hasn't moved, and still has its own input queued up
+        assert_eq!(program.memory, vec![3, 0, 1, 0, 0, 0, 99]);
+        assert_eq!(program.pointer_idx, 0);
+        assert_eq!(program.inputs, VecDeque::from(vec![21]));
+
+        assert_eq!(program.run_until_output().unwrap(), None);
+        assert_eq!(program.memory[0], 42);
+        assert_eq!(forked_program.memory[0], 42);
+    }
+
+    #[test]
+    fn intcode_run_until_output_test() {
+        // outputs 7 then 8 then halts
+        let mut program = Program::new(vec![104, 7, 104, 8, 99]);
+
+        assert_eq!(program.run_until_output().unwrap(), Some(7));
+        assert_eq!(program.pointer_idx, 2);
+        assert_eq!(program.run_until_output().unwrap(), Some(8));
+        assert_eq!(program.run_until_output().unwrap(), None);
+        assert_eq!(program.output_count(), 2);
+
+        let mut program = Program::new(vec![104, 7, 104, 8, 99]);
+        assert_eq!(program.run_to_completion().unwrap(), vec![7, 8]);
+    }
+
+    #[test]
+    fn intcode_step_test() {
+        // 2 + 3 into address 0, output it, halt
+        let mut program = Program::new(vec![1101, 2, 3, 0, 4, 0, 99]);
+
+        assert_eq!(program.step().unwrap(), HaltReason::Stepped);
+        assert_eq!(program.memory, vec![5, 2, 3, 0, 4, 0, 99]);
+        assert_eq!(program.pointer_idx, 4);
+
+        assert_eq!(program.step().unwrap(), HaltReason::Output(5));
+        assert_eq!(program.pointer_idx, 6);
+
+        assert_eq!(program.step().unwrap(), HaltReason::Halted);
+        assert_eq!(program.pointer_idx, 6);
+        assert_eq!(program.step().unwrap(), HaltReason::Halted);
+
+        // waiting on an input doesn't move the pointer
+        let mut program = Program::new(vec![3, 0, 99]);
+        assert_eq!(program.step().unwrap(), HaltReason::NeedsInput);
+        assert_eq!(program.pointer_idx, 0);
+
+        program.add_input(8);
+        assert_eq!(program.step().unwrap(), HaltReason::Stepped);
+        assert_eq!(program.memory, vec![8, 0, 99]);
+        assert!(program.run_until_output().unwrap().is_none());
+    }
+
+    #[test]
+    fn intcode_instruction_limit_test() {
+        set_instruction_limit(Some(100));
+        let mut program = Program::new(vec![1105, 1, 0]);
+        set_instruction_limit(None);
+
+        match program.run_to_completion() {
+            Err(e) => assert_eq!(e.to_string(), "Hit the instruction limit of 100 instructions"),
+            Ok(_) => panic!("An infinite loop shouldn't complete")
+        }
+        assert_eq!(program.instructions_run, 100);
+
+        // only programs created while the limit is set have it
+        let mut program = Program::new(vec![1101, 2, 3, 0, 99]);
+        assert_eq!(program.instruction_limit, None);
+        assert!(program.run_to_completion().is_ok());
+    }
+
+    #[test]
+    fn intcode_opcode_histogram_test() {
+        // counts down from 3, outputting each value, then halts
+        let memory = vec![
+            3, 100, 4, 100, 1001, 100, -1, 100, 1005, 100, 2, 99
+        ];
+
+        set_profiling(true);
+        let mut program = Program::new(memory.clone());
+        set_profiling(false);
+
+        // waiting on an input doesn't count as running the 3
+        assert_eq!(program.step().unwrap(), HaltReason::NeedsInput);
+        assert_eq!(program.opcode_histogram(), [0; 10]);
+
+        program.add_input(3);
+        assert_eq!(program.run_to_completion().unwrap(), vec![3, 2, 1]);
+        assert_eq!(program.opcode_histogram(), [1, 3, 0, 1, 3, 3, 0, 0, 0, 0]);
+
+        // not profiled, so nothing is counted
+        let mut program = Program::new(memory);
+        program.add_input(3);
+        assert_eq!(program.run_to_completion().unwrap(), vec![3, 2, 1]);
+        assert_eq!(program.opcode_histogram(), [0; 10]);
+    }
+
+    #[test]
+    fn intcode_io_transcript_test() {
+        // echoes two inputs back, adding one to the second
+        let memory = vec![3, 20, 4, 20, 3, 20, 101, 1, 20, 20, 4, 20, 99];
+
+        set_io_recording(true);
+        let mut program = Program::new(memory.clone());
+        set_io_recording(false);
+
+        program.add_input(7);
+        program.add_input(41);
+        assert_eq!(program.run_to_completion().unwrap(), vec![7, 42]);
+        assert_eq!(
+            program.io_transcript(),
+            "step 1: input 7\n\
+             step 2: output 7\n\
+             step 3: input 41\n\
+             step 5: output 42\n"
+        );
+
+        let mut program = Program::new(memory);
+        program.add_input(7);
+        program.add_input(41);
+        assert_eq!(program.run_to_completion().unwrap(), vec![7, 42]);
+        assert_eq!(program.io_transcript(), "");
+    }
+
+    #[test]
+    fn intcode_immediate_store_test() {
+        // 103 is an input stored in immediate mode; 10003 would only set the mode of a third
+        // parameter that opcode 3 doesn't have, so it's an ordinary position mode store
+        let mut program = Program::new(vec![103, 0, 99]);
+        program.add_input(1);
+        match program.run_to_completion() {
+            Err(e) => assert_eq!(e.to_string(), "opcode 3 cannot use immediate mode"),
+            Ok(_) => panic!("An immediate mode store shouldn't run")
+        }
+
+        let mut program = Program::new(vec![10003, 0, 99]);
+        program.add_input(1);
+        assert!(program.run_to_completion().is_ok());
+        assert_eq!(program.memory[0], 1);
+
+        let mut program = Program::new(vec![11101, 2, 3, 0, 99]);
+        match program.run_to_completion() {
+            Err(e) => assert_eq!(e.to_string(), "opcode 1 cannot use immediate mode"),
+            Ok(_) => panic!("An immediate mode store shouldn't run")
+        }
+    }
+
+    #[test]
+    fn intcode_negative_address_test() {
+        // moves the relative base to -5 and then stores an input there
+        let mut program = Program::new(vec![109, -5, 203, 0, 99]);
+        program.add_input(1);
+        match program.run_to_completion() {
+            Err(e) => assert_eq!(e.to_string(), "negative memory address -5"),
+            Ok(_) => panic!("A store to a negative address shouldn't run")
+        }
+
+        // and reading from one
+        let mut program = Program::new(vec![4, -1, 99]);
+        assert!(program.run_to_completion().is_err());
+
+        // or jumping to one
+        let mut program = Program::new(vec![1105, 1, -3, 99]);
+        match program.run_to_completion() {
+            Err(e) => assert_eq!(e.to_string(), "negative memory address -3"),
+            Ok(_) => panic!("A jump to a negative address shouldn't run")
+        }
+        let mut program = Program::new(vec![1106, 0, -3, 99]);
+        assert!(program.run_to_completion().is_err());
+    }
+
+    #[test]
+    fn intcode_runs_off_the_end_test() {
+        // no 99 after the add
+        let mut program = Program::new(vec![1101, 1, 1, 0]);
+        match program.run_to_completion() {
+            Err(e) => assert_eq!(e.to_string(), "instruction pointer 4 is past the end of memory"),
+            Ok(_) => panic!("A program without a 99 shouldn't halt")
+        }
+
+        // jumping past the end
+        let mut program = Program::new(vec![1105, 1, 100]);
+        assert!(program.run_to_completion().is_err());
+
+        // the operand of the last instruction is missing, so it reads as 0
+        let mut program = Program::new(vec![4]);
+        assert_eq!(program.run_until_output().unwrap(), Some(4));
+        assert!(program.run_until_output().is_err());
+    }
+
+    #[test]
+    fn intcode_disassemble_test() {
+        let program = Program::new(vec![1101, 2, 3, 0, 4, 0, 99]);
+        assert_eq!(
+            program.disassemble(),
+            vec!["0: add 2, 3, [0]", "4: out [0]", "6: halt"]
+        );
+
+        // stops at the -1, which isn't an instruction, and at the out that's missing its operand
+        let program = Program::new(vec![109, -3, 21107, 1, 2, 3, -1, 4]);
+        assert_eq!(program.disassemble(), vec!["0: arb -3", "2: lt 1, 2, [rb+3]"]);
+        let program = Program::new(vec![1006, 0, 4, 4]);
+        assert_eq!(program.disassemble(), vec!["0: jz [0], 4"]);
+    }
+
+    #[test]
+    fn intcode_run_until_blocked_test() {
+        // a outputs 1, 2 and 3 then halts; b outputs ten times each input, forever
+        let mut a = Program::new(vec![104, 1, 104, 2, 104, 3, 99]);
+        let mut b = Program::new(vec![3, 20, 1002, 20, 10, 20, 4, 20, 1105, 1, 0]);
+
+        // b has nothing to read yet, and asking again doesn't run anything
+        assert_eq!(b.run_until_blocked().unwrap(), HaltReason::NeedsInput);
+        assert_eq!(b.run_until_blocked().unwrap(), HaltReason::NeedsInput);
+        assert_eq!(b.instructions_run, 0);
+
+        // round robin between the two until neither can do anything else
+        let mut received = vec![];
+        let mut a_halted = false;
+        loop {
+            let a_state = if a_halted { HaltReason::Halted } else { a.run_until_blocked().unwrap() };
+            match a_state {
+                HaltReason::Output(val) => b.add_input(val),
+                HaltReason::Halted => a_halted = true,
+                other => panic!("a shouldn't stop for {:?}", other)
+            }
+
+            loop {
+                match b.run_until_blocked().unwrap() {
+                    HaltReason::Output(val) => received.push(val),
+                    HaltReason::NeedsInput => break,
+                    other => panic!("b shouldn't stop for {:?}", other)
+                }
+            }
+
+            if a_halted {
+                break;
+            }
+        }
+
+        assert_eq!(received, vec![10, 20, 30]);
+        assert_eq!(b.instructions_run, 3 * 4);
+    }
+
+    #[test]
+    fn intcode_from_slice_test() {
+        // adds 2 and 3 into address 0, then outputs it
+        let memory = vec![1101, 2, 3, 0, 4, 0, 99];
+
+        for _ in 0..10 {
+            let mut program = Program::from_slice(&memory);
+            assert_eq!(program.run_to_completion().unwrap(), vec![5]);
+            assert_eq!(program.modified_addresses(), vec![0]);
+        }
+        assert_eq!(memory, vec![1101, 2, 3, 0, 4, 0, 99]);
+
+        let image: Rc<[i64]> = Rc::from(memory);
+        let mut programs: Vec<Program> = (0..10).map(|_| Program::from_image(image.clone())).collect();
+        programs.push(programs[0].fork());
+        assert_eq!(Rc::strong_count(&image), 12);
+
+        for program in programs.iter_mut() {
+            assert_eq!(program.run_to_completion().unwrap(), vec![5]);
+        }
+        assert_eq!(&image[..], &[1101, 2, 3, 0, 4, 0, 99]);
+    }
+
+    #[test]
+    fn intcode_step_until_event_test() {
+        // doubles its input
+        let mut program = Program::new(vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0]);
+
+        assert_eq!(program.step_until_event().unwrap(), StepResult::NeedInput);
+        assert_eq!(program.step_until_event().unwrap(), StepResult::NeedInput);
+        program.add_input(21);
+        assert_eq!(program.step_until_event().unwrap(), StepResult::Output(42));
+        assert_eq!(program.step_until_event().unwrap(), StepResult::Halted);
+
+        // run_until_output still treats a missing input as an error
+        let mut program = Program::new(vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0]);
+        assert!(program.run_until_output().is_err());
+    }
+
+    #[test]
+    fn intcode_memory_mut_test() {
+        // outputs 3 + 4, or 3 * 4 once the add is patched into a multiply
+        let mut computer = Computer::new(vec![1, 7, 8, 0, 4, 0, 99, 3, 4]);
+        computer.memory_mut()[0] = 2;
+
+        assert_eq!(computer.run_until_output().unwrap(), Some(12));
+        assert_eq!(computer.run_until_output().unwrap(), None);
+        assert_eq!(computer.modified_addresses(), vec![0]);
+    }
+
+    #[test]
+    fn intcode_run_until_test() {
+        let mut program = Program::new(vec![104, 65, 104, 10, 104, 1000, 104, 66, 104, 2000, 99]);
+
+        assert_eq!(program.run_until(|output| output > 255).unwrap(), Some(1000));
+        // carries on from just after the match
+        assert_eq!(program.run_until_output().unwrap(), Some(66));
+        assert_eq!(program.run_until(|output| output > 255).unwrap(), Some(2000));
+        assert_eq!(program.run_until(|output| output > 255).unwrap(), None);
+
+        let mut program = Program::new(vec![104, 65, 99]);
+        assert_eq!(program.run_until(|output| output > 255).unwrap(), None);
+    }
+
+    #[test]
+    fn intcode_run_ascii_program_test() {
+        // echoes its input until it reads a '.', then prints 1000 and halts
+        let echo = vec![
+            3, 100,
+            4, 100,
+            1008, 100, 46, 101,
+            1005, 101, 14,
+            1105, 1, 0,
+            104, 1000,
+            99
+        ];
+
+        let mut program = Program::new(echo.clone());
+        assert_eq!(
+            program.run_ascii_program(&["hello", "world", "."]).unwrap(),
+            ("hello\nworld\n.".to_string(), Some(1000))
+        );
+
+        // without the final large value
+        let mut quiet_echo = echo;
+        quiet_echo[14] = 99;
+        let mut program = Program::new(quiet_echo);
+        assert_eq!(
+            program.run_ascii_program(&["ab."]).unwrap(),
+            ("ab.".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn intcode_prompt_test() {
+        // prints "Main:" and a newline, then the dust count
+        let mut memory: Vec<i64> = "Main:\n".chars().flat_map(|c| vec![104, c as i64]).collect();
+        memory.extend(vec![104, 1234, 99]);
+
+        let mut program = Program::new(memory);
+        let (transcript, dust) = program.run_ascii_program(&["A,B"]).unwrap();
+
+        assert_eq!(dust, Some(1234));
+        assert_eq!(transcript, "Main:\n");
+        assert_eq!(without_prompts(&transcript), "");
+        assert_eq!(without_prompts("Input instructions:\n\nWalking...\n"), "\nWalking...\n");
+    }
+
+    #[derive(Clone)]
+    struct Constant(i64);
+
+    impl InputSource for Constant {
+        fn next_input(&mut self) -> Result<i64> {
+            Ok(self.0)
+        }
+
+        fn box_clone(&self) -> Box<dyn InputSource> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn intcode_input_source_test() {
+        // adds together two inputs
+        let memory = vec![3, 13, 3, 14, 1, 13, 14, 15, 4, 15, 99, 0, 0, 0, 0, 0];
+
+        let mut constant_program = Program::new(memory.clone());
+        constant_program.set_input_source(Constant(5));
+        assert_eq!(constant_program.run_to_completion().unwrap(), vec![10]);
+
+        let mut scripted_program = Program::new(memory.clone());
+        scripted_program.set_input_source(VecDeque::from(vec![3, 4]));
+        assert_eq!(scripted_program.run_to_completion().unwrap(), vec![7]);
+
+        // queued inputs come first
+        let mut mixed_program = Program::new(memory);
+        mixed_program.add_input(100);
+        mixed_program.set_input_source(Constant(5));
+        assert_eq!(mixed_program.run_to_completion().unwrap(), vec![105]);
+    }
+
+    #[test]
+    fn intcode_modified_addresses_test() {
+        let mut program = Program::new(vec![1, 0, 0, 0, 99]);
+        assert!(program.modified_addresses().is_empty());
+
+        program.run_to_completion().unwrap();
+        assert_eq!(program.modified_addresses(), vec![0]);
+
+        // writing past the end of the program
+        let mut program = Program::new(vec![1101, 2, 3, 10, 1101, 0, 0, 9, 99]);
+        program.run_to_completion().unwrap();
+        assert_eq!(program.memory().len(), 11);
+        assert_eq!(program.modified_addresses(), vec![10]);
+    }
+
+    #[test]
+    fn intcode_read_mem_test() {
+        let mut program = Program::new(vec![1, 0, 0, 0, 99]);
+        program.run_to_completion().unwrap();
+
+        assert_eq!(program.read_mem(0), 2);
+        assert_eq!(program.memory(), &[2, 0, 0, 0, 99]);
+
+        assert_eq!(program.read_mem(9), 0);
+        assert_eq!(program.memory().len(), 10);
+    }
+}
@@ -4,14 +4,105 @@ extern crate lazy_static;
 extern crate itertools;
 extern crate regex;
 
+use std::env;
+use std::path::PathBuf;
 use std::time::Instant;
 
+#[allow(dead_code)]
+mod intcode;
+
+#[allow(dead_code)]
+mod math;
+
+#[allow(dead_code)]
+mod tile_map;
+
+#[allow(dead_code)]
+mod dense_grid;
+
+#[allow(dead_code)]
+mod debug;
+
+mod answer;
+
+mod answer_cache;
+
 mod aoc_problems;
 
 fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // --batch <dir> runs the day over every file in dir instead of its usual input
+    let batch_dir = match args.iter().position(|arg| arg == "--batch") {
+        Some(idx) if idx + 1 < args.len() => {
+            let dir = PathBuf::from(args.remove(idx + 1));
+            args.remove(idx);
+            Some(dir)
+        },
+        Some(_) => {
+            eprintln!("Error: --batch needs a directory");
+            return;
+        },
+        None => None
+    };
+
+    // --no-cache always solves the day, rather than reusing an answer from ./cache
+    let use_cache = match args.iter().position(|arg| arg == "--no-cache") {
+        Some(idx) => {
+            args.remove(idx);
+            false
+        },
+        None => true
+    };
+
+    // --verbose prints each day's working out to stderr as well as the answer
+    if let Some(idx) = args.iter().position(|arg| arg == "--verbose") {
+        args.remove(idx);
+        debug::set_verbose(true);
+    }
+
+    let (day, part) = match aoc_problems::day_and_part_from_args(&mut args) {
+        Ok(day_and_part) => day_and_part,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+
+    let limits = match aoc_problems::Limits::from_args(&args) {
+        Ok(limits) => limits,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+
+    if let Some(dir) = batch_dir {
+        let results = aoc_problems::input_files(&dir)
+            .and_then(|files| aoc_problems::run_batch(day, part, &files, limits));
+        match results {
+            Ok(results) => print!("{}", aoc_problems::batch_table(&results)),
+            Err(e) => eprintln!("Error: {}", e)
+        }
+        return;
+    }
+
     let now = Instant::now();
-    let result = aoc_problems::day_21::q2("./inputs/day21.txt".to_string());
-    let elapsed = now.elapsed();
-    println!("Answer: {:?}", result);
-    println!("Elapsed time: {:?}", elapsed);
+    let solve = move || {
+        let registry = aoc_problems::registry();
+        let (ref q1, ref q2) = registry[&day];
+        let fname = aoc_problems::input_path(day).display().to_string();
+        if part == 1 { q1(fname) } else { q2(fname) }
+    };
+    let result = if use_cache {
+        aoc_problems::load_day_input(day).and_then(|input| {
+            answer_cache::AnswerCache::new("./cache")
+                .get_or_compute(day, part, &input, || aoc_problems::run_with_limits(solve, limits))
+        })
+    } else {
+        aoc_problems::run_with_limits(solve, limits)
+    };
+    let (stdout, stderr) = aoc_problems::report(&result, now.elapsed());
+    print!("{}", stdout);
+    eprint!("{}", stderr);
 }
@@ -0,0 +1,74 @@
+use std::ops::{Div, Mul, Rem};
+
+// The integer types gcd and lcm work on
+pub trait Integer: Copy + PartialEq + Rem<Output = Self> + Mul<Output = Self> + Div<Output = Self> {
+    fn zero() -> Self;
+
+    // the identity for unsigned types
+    fn magnitude(self) -> Self;
+}
+
+macro_rules! impl_signed_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Integer for $t {
+                fn zero() -> $t { 0 }
+                fn magnitude(self) -> $t { self.abs() }
+            }
+        )*
+    }
+}
+
+macro_rules! impl_unsigned_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Integer for $t {
+                fn zero() -> $t { 0 }
+                fn magnitude(self) -> $t { self }
+            }
+        )*
+    }
+}
+
+impl_signed_integer!(i32, i64);
+impl_unsigned_integer!(u32, u64, usize);
+
+// Always non-negative, and gcd(0, n) is |n|
+pub fn gcd<T: Integer>(m: T, n: T) -> T {
+    if m == T::zero() {
+        n.magnitude()
+    } else {
+        gcd(n % m, m)
+    }
+}
+
+pub fn lcm<T: Integer>(m: T, n: T) -> T {
+    if m == T::zero() || n == T::zero() {
+        return T::zero();
+    }
+
+    // dividing first keeps the intermediate value as small as possible
+    (m / gcd(m, n) * n).magnitude()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gcd_test() {
+        assert_eq!(gcd(48, 18), 6);
+        assert_eq!(gcd(18usize, 48usize), 6);
+        assert_eq!(gcd(-4, 6), 2);
+        assert_eq!(gcd(0, -5), 5);
+        assert_eq!(gcd(0u32, 0u32), 0);
+    }
+
+    #[test]
+    fn lcm_test() {
+        assert_eq!(lcm(4, 6), 12);
+        assert_eq!(lcm(4usize, 6usize), 12);
+        assert_eq!(lcm(-4i64, 6i64), 12);
+        assert_eq!(lcm(0, 6), 0);
+    }
+}
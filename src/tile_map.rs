@@ -0,0 +1,106 @@
+use std::fmt;
+
+use std::collections::BTreeMap;
+
+// Sparse grid of tiles used for rendering the maps that come out of the Intcode days.
+// Each day converts its own Coordinate into (x, y) and supplies its own tile type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TileMap<T> {
+    tiles: BTreeMap<(i64, i64), T>,
+    blank: char,
+    y_up: bool
+}
+
+impl<T> TileMap<T> {
+    // blank is drawn wherever there's no tile; y_up draws the largest y on the top row
+    pub fn new(blank: char, y_up: bool) -> TileMap<T> {
+        TileMap {
+            tiles: BTreeMap::new(),
+            blank,
+            y_up
+        }
+    }
+
+    pub fn insert(&mut self, x: i64, y: i64, tile: T) {
+        self.tiles.insert((x, y), tile);
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> Option<&T> {
+        self.tiles.get(&(x, y))
+    }
+
+    // ((min_x, min_y), (max_x, max_y)), or None if there aren't any tiles
+    pub fn bounding_box(&self) -> Option<((i64, i64), (i64, i64))> {
+        let min_x = self.tiles.keys().map(|&(x, _)| x).min()?;
+        let max_x = self.tiles.keys().map(|&(x, _)| x).max()?;
+        let min_y = self.tiles.keys().map(|&(_, y)| y).min()?;
+        let max_y = self.tiles.keys().map(|&(_, y)| y).max()?;
+
+        Some(((min_x, min_y), (max_x, max_y)))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for TileMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ((min_x, min_y), (max_x, max_y)) = match self.bounding_box() {
+            Some(bounding_box) => bounding_box,
+            None => return Ok(())
+        };
+
+        let rows: Vec<i64> = if self.y_up {
+            (min_y..=max_y).rev().collect()
+        } else {
+            (min_y..=max_y).collect()
+        };
+
+        for y in rows {
+            for x in min_x..=max_x {
+                match self.get(x, y) {
+                    Some(tile) => write!(f, "{}", tile)?,
+                    None => write!(f, "{}", self.blank)?
+                }
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_map_display_test() {
+        let mut tile_map: TileMap<char> = TileMap::new(' ', false);
+        tile_map.insert(0, 0, '#');
+        tile_map.insert(2, 0, '#');
+        tile_map.insert(1, 1, '.');
+        tile_map.insert(-1, 1, '@');
+
+        assert_eq!(
+            tile_map.bounding_box(),
+            Some(((-1, 0), (2, 1)))
+        );
+        assert_eq!(
+            tile_map.to_string(),
+            " # #\n@ . \n"
+        );
+
+        let mut flipped_tile_map: TileMap<char> = TileMap::new(' ', true);
+        flipped_tile_map.tiles = tile_map.tiles.clone();
+        assert_eq!(
+            flipped_tile_map.to_string(),
+            "@ . \n # #\n"
+        );
+    }
+
+    #[test]
+    fn tile_map_empty_test() {
+        let tile_map: TileMap<char> = TileMap::new(' ', false);
+
+        assert_eq!(tile_map.bounding_box(), None);
+        assert_eq!(tile_map.to_string(), "");
+    }
+}
@@ -0,0 +1,32 @@
+use std::process::{Command, Output};
+
+// Runs the aoc_2019 binary like it would be from the shell, in the crate root so that
+// ./inputs is where it expects
+fn aoc_2019(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aoc_2019"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn cli_only_answer_on_stdout_test() {
+    // day 6 part 2 prints the orbits it walks through when asked to
+    let output = aoc_2019(&["6", "2", "--no-cache"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout, "Answer: 349\n");
+    assert!(stderr.starts_with("Elapsed time: "), "unexpected stderr: {}", stderr);
+    assert_eq!(stderr.lines().count(), 1);
+
+    let output = aoc_2019(&["6", "2", "--no-cache", "--verbose"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(stdout, "Answer: 349\n");
+    assert!(stderr.contains("Parent of YOU = "));
+    assert!(stderr.lines().last().unwrap().starts_with("Elapsed time: "));
+}